@@ -1,6 +1,9 @@
 use std::env;
 
 fn main() {
+    println!("cargo:rustc-check-cfg=cfg(finchers_deny_warnings)");
+    println!("cargo:rustc-check-cfg=cfg(finchers_inject_extern_prelude)");
+
     if env::var_os("FINCHERS_DENY_WARNINGS").is_some() {
         println!("cargo:rustc-cfg=finchers_deny_warnings");
     }
@@ -0,0 +1,17 @@
+extern crate finchers_template;
+
+use finchers_template::backend::TemplateDir;
+use std::env;
+use std::path::Path;
+
+fn main() {
+    let dir = TemplateDir::new("templates").extension("html");
+
+    // Parse all templates here, so that syntax errors fail the build.
+    if let Err(err) = dir.load_tera() {
+        panic!("{}", err);
+    }
+
+    let out_file = Path::new(&env::var("OUT_DIR").unwrap()).join("templates.rs");
+    dir.embed(out_file).unwrap();
+}
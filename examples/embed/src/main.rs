@@ -0,0 +1,61 @@
+#[macro_use]
+extern crate finchers;
+extern crate finchers_template;
+#[macro_use]
+extern crate log;
+extern crate pretty_env_logger;
+#[macro_use]
+extern crate serde;
+
+use finchers::prelude::*;
+
+use finchers_template::backend::EmbeddedTemplates;
+
+/// The templates under `templates/`, embedded by the build script.
+static TEMPLATES: EmbeddedTemplates = include!(concat!(env!("OUT_DIR"), "/templates.rs"));
+
+#[derive(Debug, Serialize)]
+struct UserInfo {
+    name: String,
+}
+
+fn main() {
+    pretty_env_logger::init();
+
+    let engine = TEMPLATES.load_tera().unwrap_or_else(|e| panic!("{}", e));
+
+    let endpoint = {
+        path!(@get /)
+            .map(|| UserInfo {
+                name: "Alice".into(),
+            }).wrap(finchers_template::tera(engine, "index.html"))
+    };
+
+    info!("Listening on http://127.0.0.1:4000");
+    finchers::server::start(endpoint)
+        .serve("127.0.0.1:4000")
+        .unwrap_or_else(|e| error!("{}", e));
+}
+
+#[test]
+fn test_embedded_templates() {
+    use finchers::test;
+
+    assert!(TEMPLATES.get("index.html").is_some());
+    assert!(TEMPLATES.get("layouts/base.html").is_some());
+
+    let engine = TEMPLATES.load_tera().unwrap();
+    let endpoint = {
+        path!(@get /)
+            .map(|| UserInfo {
+                name: "Alice".into(),
+            }).wrap(finchers_template::tera(engine, "index.html"))
+    };
+
+    let mut runner = test::runner(endpoint);
+    let response = runner.perform("/").unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.body().to_utf8().unwrap();
+    assert!(body.contains("<title>Greeting</title>"));
+    assert!(body.contains("Hello, Alice."));
+}
@@ -1,11 +1,13 @@
 #![cfg(feature = "use-askama")]
 
-use super::engine::{Engine, EngineImpl};
+use super::engine::{Engine, StreamingEngine};
 use renderer::Renderer;
 
 use askama::Template;
 use http::header::HeaderValue;
-use mime_guess::get_mime_type_str;
+use mime_guess::from_ext;
+use std::fmt;
+use std::io;
 use std::marker::PhantomData;
 
 pub fn askama<CtxT: Template>() -> Renderer<AskamaEngine<CtxT>> {
@@ -31,15 +33,14 @@ impl<CtxT: Template> AskamaEngine<CtxT> {
     /// Precompute the value of content-type by using the given instance of context.
     pub fn precompute_content_type(&mut self, hint: &CtxT) {
         self.content_type_cache = hint.extension().and_then(|ext| {
-            get_mime_type_str(ext)
+            from_ext(ext)
+                .first_raw()
                 .map(|mime_str| mime_str.parse().expect("should be a valid header value"))
         });
     }
 }
 
-impl<CtxT: Template> Engine<CtxT> for AskamaEngine<CtxT> {}
-
-impl<CtxT: Template> EngineImpl<CtxT> for AskamaEngine<CtxT> {
+impl<CtxT: Template> Engine<CtxT> for AskamaEngine<CtxT> {
     type Body = String;
     type Error = ::askama::Error;
 
@@ -47,7 +48,7 @@ impl<CtxT: Template> EngineImpl<CtxT> for AskamaEngine<CtxT> {
     fn content_type_hint(&self, value: &CtxT) -> Option<HeaderValue> {
         self.content_type_cache.clone().or_else(|| {
             let ext = value.extension()?;
-            get_mime_type_str(ext)?.parse().ok()
+            from_ext(ext).first_raw()?.parse().ok()
         })
     }
 
@@ -56,10 +57,26 @@ impl<CtxT: Template> EngineImpl<CtxT> for AskamaEngine<CtxT> {
     }
 }
 
+impl<CtxT: Template> StreamingEngine<CtxT> for AskamaEngine<CtxT> {
+    fn render_to_write(&self, value: CtxT, writer: &mut dyn io::Write) -> Result<(), Self::Error> {
+        value.render_into(&mut IoWriter { writer })
+    }
+}
+
+/// An adapter for writing the output of `Template::render_into` to an `io::Write`.
+struct IoWriter<'a> {
+    writer: &'a mut dyn io::Write,
+}
+
+impl<'a> fmt::Write for IoWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.writer.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
 #[test]
 fn test_askama() {
     use askama::Error;
-    use std::fmt;
 
     #[derive(Debug)]
     struct Context {
@@ -89,4 +106,9 @@ fn test_askama() {
         engine.render(value),
         Ok(ref body) if body == "Alice"
     );
+
+    let value = Context { name: "Bob".into() };
+    let mut body = vec![];
+    engine.render_to_write(value, &mut body).unwrap();
+    assert_eq!(body, b"Bob");
 }
@@ -0,0 +1,318 @@
+use bytes::Bytes;
+use either::Either;
+use failure;
+use http::header::HeaderValue;
+use std::fmt;
+
+use super::engine::{Engine, Validate};
+
+type DynEngine<CtxT> = dyn ErasedEngine<CtxT> + Send + Sync + 'static;
+type ValidateFn<Eng> = fn(&Eng) -> Result<(), failure::Error>;
+
+/// A type-erased template engine.
+///
+/// The bodies rendered by the inner engine are converted into `Bytes` and the
+/// errors into `failure::Error`, so the renderers using different engines can be
+/// stored in a collection or chosen at runtime:
+///
+/// ```ignore
+/// let renderer: Renderer<BoxEngine<Context>> = match config.engine {
+///     "tera" => finchers_template::tera(tera, "index.html").boxed(),
+///     _ => finchers_template::handlebars(handlebars, "index.html").boxed(),
+/// };
+/// ```
+///
+/// The whole body is buffered, so the streaming bodies are not supported.
+/// The validation of the inner engine is forwarded only if the value is created
+/// by `with_validation`. Otherwise, `validate` always returns an error so that
+/// the check is not skipped silently.
+pub struct BoxEngine<CtxT> {
+    inner: Box<DynEngine<CtxT>>,
+}
+
+impl<CtxT> fmt::Debug for BoxEngine<CtxT> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxEngine").finish()
+    }
+}
+
+impl<CtxT> BoxEngine<CtxT> {
+    /// Create a new `BoxEngine` from the specified engine.
+    pub fn new<Eng>(engine: Eng) -> BoxEngine<CtxT>
+    where
+        Eng: Engine<CtxT> + Send + Sync + 'static,
+        Eng::Body: BoxBody,
+    {
+        BoxEngine {
+            inner: Box::new(Erased {
+                engine,
+                validate: None,
+            }),
+        }
+    }
+
+    /// Create a new `BoxEngine` which forwards `Validate` to the specified engine.
+    pub fn with_validation<Eng>(engine: Eng) -> BoxEngine<CtxT>
+    where
+        Eng: Engine<CtxT> + Validate + Send + Sync + 'static,
+        Eng::Body: BoxBody,
+    {
+        BoxEngine {
+            inner: Box::new(Erased {
+                engine,
+                validate: Some(Eng::validate),
+            }),
+        }
+    }
+}
+
+impl<CtxT> Engine<CtxT> for BoxEngine<CtxT> {
+    type Body = Bytes;
+    type Error = failure::Error;
+
+    fn content_type_hint(&self, value: &CtxT) -> Option<HeaderValue> {
+        self.inner.content_type_hint(value)
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        self.inner.render(value)
+    }
+
+    fn render_with_hint(
+        &self,
+        value: CtxT,
+    ) -> Result<(Option<HeaderValue>, Self::Body), Self::Error> {
+        self.inner.render_with_hint(value)
+    }
+}
+
+impl<CtxT> Validate for BoxEngine<CtxT> {
+    fn validate(&self) -> Result<(), failure::Error> {
+        ErasedEngine::validate(&*self.inner)
+    }
+}
+
+/// A trait representing a response body which can be buffered by `BoxEngine`.
+pub trait BoxBody {
+    /// Converts itself into the buffered bytes.
+    fn into_bytes(self) -> Bytes;
+}
+
+impl BoxBody for Bytes {
+    fn into_bytes(self) -> Bytes {
+        self
+    }
+}
+
+impl BoxBody for Vec<u8> {
+    fn into_bytes(self) -> Bytes {
+        self.into()
+    }
+}
+
+impl BoxBody for String {
+    fn into_bytes(self) -> Bytes {
+        self.into()
+    }
+}
+
+impl BoxBody for &'static str {
+    fn into_bytes(self) -> Bytes {
+        self.into()
+    }
+}
+
+impl BoxBody for &'static [u8] {
+    fn into_bytes(self) -> Bytes {
+        self.into()
+    }
+}
+
+impl<L: BoxBody, R: BoxBody> BoxBody for Either<L, R> {
+    fn into_bytes(self) -> Bytes {
+        match self {
+            Either::Left(l) => l.into_bytes(),
+            Either::Right(r) => r.into_bytes(),
+        }
+    }
+}
+
+trait ErasedEngine<CtxT>: Engine<CtxT, Body = Bytes, Error = failure::Error> {
+    fn validate(&self) -> Result<(), failure::Error>;
+}
+
+/// An adapter which converts the body and error of the inner engine.
+struct Erased<Eng> {
+    engine: Eng,
+    validate: Option<ValidateFn<Eng>>,
+}
+
+impl<Eng, CtxT> Engine<CtxT> for Erased<Eng>
+where
+    Eng: Engine<CtxT>,
+    Eng::Body: BoxBody,
+{
+    type Body = Bytes;
+    type Error = failure::Error;
+
+    fn content_type_hint(&self, value: &CtxT) -> Option<HeaderValue> {
+        self.engine.content_type_hint(value)
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        self.engine
+            .render(value)
+            .map(BoxBody::into_bytes)
+            .map_err(Into::into)
+    }
+
+    fn render_with_hint(
+        &self,
+        value: CtxT,
+    ) -> Result<(Option<HeaderValue>, Self::Body), Self::Error> {
+        self.engine
+            .render_with_hint(value)
+            .map(|(content_type, body)| (content_type, body.into_bytes()))
+            .map_err(Into::into)
+    }
+}
+
+impl<Eng, CtxT> ErasedEngine<CtxT> for Erased<Eng>
+where
+    Eng: Engine<CtxT>,
+    Eng::Body: BoxBody,
+{
+    fn validate(&self) -> Result<(), failure::Error> {
+        match self.validate {
+            Some(validate) => validate(&self.engine),
+            None => Err(failure::err_msg(
+                "the engine does not support the validation (use `BoxEngine::with_validation`)",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BoxEngine;
+    use backend::combinator::EitherEngine;
+    use backend::engine::{Engine, Validate};
+    use either::Either;
+    use renderer::Renderer;
+
+    use finchers::error::Never;
+    use finchers::prelude::*;
+    use finchers::test;
+    use http::header::HeaderValue;
+    use mime;
+
+    #[derive(Debug)]
+    struct UpperEngine;
+    impl Engine<&'static str> for UpperEngine {
+        type Body = String;
+        type Error = Never;
+        fn content_type_hint(&self, _: &&'static str) -> Option<HeaderValue> {
+            Some(HeaderValue::from_static("text/plain; charset=utf-8"))
+        }
+        fn render(&self, value: &'static str) -> Result<Self::Body, Self::Error> {
+            Ok(value.to_uppercase())
+        }
+    }
+
+    #[derive(Debug)]
+    struct BytesEngine;
+    impl Engine<&'static str> for BytesEngine {
+        type Body = Vec<u8>;
+        type Error = ::std::io::Error;
+        #[allow(clippy::io_other_error)] // `io::Error::other` requires Rust 1.74
+        fn render(&self, value: &'static str) -> Result<Self::Body, Self::Error> {
+            if value.is_empty() {
+                return Err(::std::io::Error::new(::std::io::ErrorKind::Other, "empty"));
+            }
+            Ok(value.as_bytes().to_vec())
+        }
+    }
+
+    #[test]
+    fn test_box_engine() {
+        let engines = [BoxEngine::new(UpperEngine), BoxEngine::new(BytesEngine)];
+
+        assert_matches!(
+            engines[0].content_type_hint(&"hello"),
+            Some(ref h) if h == "text/plain; charset=utf-8"
+        );
+        assert_eq!(engines[0].render("hello").unwrap(), "HELLO");
+
+        assert_eq!(engines[1].content_type_hint(&"hello"), None);
+        assert_eq!(engines[1].render("hello").unwrap(), "hello");
+        assert_eq!(engines[1].render("").unwrap_err().to_string(), "empty");
+    }
+
+    #[test]
+    fn test_box_engine_either_body() {
+        let engine = BoxEngine::new(EitherEngine::new(UpperEngine, BytesEngine));
+        assert_eq!(engine.render(Either::Left("hello")).unwrap(), "HELLO");
+        assert_eq!(engine.render(Either::Right("hello")).unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_boxed_renderer() {
+        let renderers: Vec<Renderer<BoxEngine<&'static str>>> = vec![
+            Renderer::new(UpperEngine).boxed(),
+            Renderer::new(BytesEngine)
+                .content_type(&mime::APPLICATION_OCTET_STREAM)
+                .boxed(),
+        ];
+
+        let mut renderers = renderers.into_iter();
+        let mut runner = test::runner(endpoint::cloned("hello").wrap(renderers.next().unwrap()));
+        let response = runner.perform("/").unwrap();
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(response.body().to_utf8().unwrap(), "HELLO");
+
+        // the content-type set before boxing is kept.
+        let mut runner = test::runner(endpoint::cloned("hello").wrap(renderers.next().unwrap()));
+        let response = runner.perform("/").unwrap();
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "application/octet-stream"
+        );
+        assert_eq!(response.body().to_utf8().unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_box_engine_validate() {
+        #[derive(Debug)]
+        struct InvalidEngine;
+        impl Engine<&'static str> for InvalidEngine {
+            type Body = String;
+            type Error = Never;
+            fn render(&self, value: &'static str) -> Result<Self::Body, Self::Error> {
+                Ok(value.into())
+            }
+        }
+        impl Validate for InvalidEngine {
+            fn validate(&self) -> Result<(), ::failure::Error> {
+                Err(::failure::err_msg("invalid"))
+            }
+        }
+
+        assert_eq!(
+            BoxEngine::new(InvalidEngine)
+                .validate()
+                .unwrap_err()
+                .to_string(),
+            "the engine does not support the validation (use `BoxEngine::with_validation`)"
+        );
+        assert_eq!(
+            BoxEngine::with_validation(InvalidEngine)
+                .validate()
+                .unwrap_err()
+                .to_string(),
+            "invalid"
+        );
+    }
+}
@@ -0,0 +1,447 @@
+use failure;
+use http::header::HeaderValue;
+#[cfg(feature = "serde-context")]
+use serde::{Serialize, Serializer};
+use std::any::{Any, TypeId};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use super::engine::{Engine, HasTemplate, Validate};
+#[cfg(feature = "serde-context")]
+use super::template_name::TemplateName;
+
+const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+/// A template engine which caches the rendered bodies of the inner engine.
+///
+/// The cache is keyed by the whole data which the context value feeds to `Hash`
+/// (not by its hash value), so the context types must implement `Hash`. The
+/// engines which choose the template at render time receive the template name
+/// as a part of the context (e.g. `Named`), so it is included in the key as
+/// well. Use `Keyed` to supply the key explicitly.
+///
+/// The entries expire after the specified TTL. The cache is bounded by the
+/// number of entries, not by the size of the bodies: the oldest entry is
+/// evicted when the number of entries reaches `max_entries`. Only the
+/// successfully rendered bodies are cached.
+#[derive(Debug)]
+pub struct CachedEngine<Eng> {
+    engine: Eng,
+    cache: CacheHandle,
+}
+
+impl<Eng> CachedEngine<Eng> {
+    /// Create a new `CachedEngine` whose entries expire after `ttl`.
+    pub fn new(engine: Eng, ttl: Duration) -> CachedEngine<Eng> {
+        CachedEngine {
+            engine,
+            cache: CacheHandle {
+                inner: Arc::new(Cache {
+                    ttl,
+                    max_entries: DEFAULT_MAX_ENTRIES,
+                    entries: Mutex::new(HashMap::new()),
+                    hits: AtomicUsize::new(0),
+                    misses: AtomicUsize::new(0),
+                }),
+            },
+        }
+    }
+
+    /// Sets the maximum number of cached entries.
+    ///
+    /// The limit counts the entries regardless of the size of their bodies.
+    /// The default value is 1024.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the cache has already been shared by `handle`.
+    pub fn max_entries(mut self, max_entries: usize) -> CachedEngine<Eng> {
+        Arc::get_mut(&mut self.cache.inner)
+            .expect("the cache has already been shared")
+            .max_entries = max_entries;
+        self
+    }
+
+    /// Returns a handle to the cache, which can be used to invalidate the entries
+    /// and retrieve the statistics.
+    pub fn handle(&self) -> CacheHandle {
+        self.cache.clone()
+    }
+
+    /// Returns a reference to the inner template engine.
+    pub fn engine(&self) -> &Eng {
+        &self.engine
+    }
+}
+
+impl<Eng, CtxT> Engine<CtxT> for CachedEngine<Eng>
+where
+    Eng: Engine<CtxT>,
+    Eng::Body: Clone + Send + Sync + 'static,
+    CtxT: Hash + 'static,
+{
+    type Body = Eng::Body;
+    type Error = Eng::Error;
+
+    fn content_type_hint(&self, value: &CtxT) -> Option<HeaderValue> {
+        self.engine.content_type_hint(value)
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        let key = cache_key(&value);
+        if let Some(body) = self.cache.get::<Eng::Body>(&key) {
+            return Ok(body);
+        }
+        let body = self.engine.render(value)?;
+        self.cache.insert(key, body.clone());
+        Ok(body)
+    }
+
+    fn render_with_hint(
+        &self,
+        value: CtxT,
+    ) -> Result<(Option<HeaderValue>, Self::Body), Self::Error> {
+        let key = cache_key(&value);
+        if let Some(body) = self.cache.get::<Eng::Body>(&key) {
+            return Ok((self.engine.content_type_hint(&value), body));
+        }
+        let (content_type, body) = self.engine.render_with_hint(value)?;
+        self.cache.insert(key, body.clone());
+        Ok((content_type, body))
+    }
+}
+
+impl<Eng: Validate> Validate for CachedEngine<Eng> {
+    fn validate(&self) -> Result<(), failure::Error> {
+        self.engine.validate()
+    }
+}
+
+impl<Eng, CtxT> HasTemplate<CtxT> for CachedEngine<Eng>
+where
+    Eng: HasTemplate<CtxT>,
+{
+    fn has_template(&self, value: &CtxT) -> bool {
+        self.engine.has_template(value)
+    }
+}
+
+fn cache_key<CtxT: Hash + 'static>(value: &CtxT) -> CacheKey {
+    let mut writer = KeyWriter(vec![]);
+    value.hash(&mut writer);
+    CacheKey {
+        type_id: TypeId::of::<CtxT>(),
+        data: writer.0,
+    }
+}
+
+/// The key of cache entries, which holds the whole data fed to `Hash` so that
+/// the distinct context values never share an entry even if their hashes collide.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    type_id: TypeId,
+    data: Vec<u8>,
+}
+
+/// A `Hasher` which records the written data instead of hashing it.
+struct KeyWriter(Vec<u8>);
+
+impl Hasher for KeyWriter {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        // The implementations of `Hash` only write the data, so the result is never used.
+        0
+    }
+}
+
+/// A handle to the cache of `CachedEngine`.
+#[derive(Debug, Clone)]
+pub struct CacheHandle {
+    inner: Arc<Cache>,
+}
+
+struct Cache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<CacheKey, Entry>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+struct Entry {
+    body: Box<dyn Any + Send + Sync>,
+    inserted_at: Instant,
+}
+
+impl fmt::Debug for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("ttl", &self.ttl)
+            .field("max_entries", &self.max_entries)
+            .field("hits", &self.hits)
+            .field("misses", &self.misses)
+            .finish()
+    }
+}
+
+impl CacheHandle {
+    fn entries(&self) -> MutexGuard<'_, HashMap<CacheKey, Entry>> {
+        self.inner
+            .entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn get<B: Clone + 'static>(&self, key: &CacheKey) -> Option<B> {
+        let mut entries = self.entries();
+        let body = match entries.get(key) {
+            Some(entry) if entry.inserted_at.elapsed() < self.inner.ttl => {
+                entry.body.downcast_ref::<B>().cloned()
+            }
+            Some(..) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        };
+        let counter = if body.is_some() {
+            &self.inner.hits
+        } else {
+            &self.inner.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        body
+    }
+
+    fn insert<B: Send + Sync + 'static>(&self, key: CacheKey, body: B) {
+        if self.inner.max_entries == 0 {
+            return;
+        }
+        let mut entries = self.entries();
+        if entries.len() >= self.inner.max_entries && !entries.contains_key(&key) {
+            let ttl = self.inner.ttl;
+            entries.retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+            if entries.len() >= self.inner.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.inserted_at)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                body: Box::new(body),
+                inserted_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Removes the entry rendered from the specified context value.
+    pub fn invalidate<CtxT: Hash + 'static>(&self, value: &CtxT) {
+        self.entries().remove(&cache_key(value));
+    }
+
+    /// Removes all entries in the cache.
+    pub fn invalidate_all(&self) {
+        self.entries().clear();
+    }
+
+    /// Returns the number of entries currently stored in the cache, including the expired ones.
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    /// Returns `true` if the cache has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of renderings served from the cache.
+    pub fn hits(&self) -> usize {
+        self.inner.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of renderings which are not served from the cache.
+    pub fn misses(&self) -> usize {
+        self.inner.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// A pair of a cache key and a context value.
+///
+/// The value is hashed only by the key, and the context value is passed
+/// as is to the template (`Serialize` and `TemplateName` are forwarded to it).
+#[derive(Debug, Clone)]
+pub struct Keyed<CtxT> {
+    key: Cow<'static, str>,
+    ctx: CtxT,
+}
+
+impl<CtxT> Keyed<CtxT> {
+    /// Create a new `Keyed` from the specified cache key and context value.
+    pub fn new(key: impl Into<Cow<'static, str>>, ctx: CtxT) -> Keyed<CtxT> {
+        Keyed {
+            key: key.into(),
+            ctx,
+        }
+    }
+
+    /// Returns the cache key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns a reference to the inner context value.
+    pub fn context(&self) -> &CtxT {
+        &self.ctx
+    }
+
+    /// Consumes itself and returns the inner context value.
+    pub fn into_context(self) -> CtxT {
+        self.ctx
+    }
+}
+
+impl<CtxT> Hash for Keyed<CtxT> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+#[cfg(feature = "serde-context")]
+impl<CtxT> TemplateName for Keyed<CtxT>
+where
+    CtxT: TemplateName,
+{
+    fn template_name(&self) -> &str {
+        self.ctx.template_name()
+    }
+}
+
+#[cfg(feature = "serde-context")]
+impl<CtxT: Serialize> Serialize for Keyed<CtxT> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.ctx.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cache_key, CachedEngine, Keyed};
+    use backend::engine::Engine;
+
+    use finchers::error::Never;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[derive(Debug, Default)]
+    struct CountingEngine {
+        count: AtomicUsize,
+    }
+    impl Engine<String> for CountingEngine {
+        type Body = String;
+        type Error = Never;
+        fn render(&self, value: String) -> Result<Self::Body, Self::Error> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            Ok(format!("<p>{}</p>", value))
+        }
+    }
+    impl Engine<Keyed<u32>> for CountingEngine {
+        type Body = String;
+        type Error = Never;
+        fn render(&self, value: Keyed<u32>) -> Result<Self::Body, Self::Error> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            Ok(value.into_context().to_string())
+        }
+    }
+
+    #[test]
+    fn test_cached_engine() {
+        let engine = CachedEngine::new(CountingEngine::default(), Duration::from_secs(60));
+        let handle = engine.handle();
+
+        assert_eq!(engine.render("Alice".to_owned()).unwrap(), "<p>Alice</p>");
+        assert_eq!(engine.render("Alice".to_owned()).unwrap(), "<p>Alice</p>");
+        assert_eq!(engine.render("Bob".to_owned()).unwrap(), "<p>Bob</p>");
+        assert_eq!(engine.engine().count.load(Ordering::SeqCst), 2);
+        assert_eq!(handle.hits(), 1);
+        assert_eq!(handle.misses(), 2);
+        assert_eq!(handle.len(), 2);
+
+        handle.invalidate(&"Alice".to_owned());
+        assert_eq!(handle.len(), 1);
+        engine.render("Alice".to_owned()).unwrap();
+        assert_eq!(engine.engine().count.load(Ordering::SeqCst), 3);
+
+        handle.invalidate_all();
+        assert!(handle.is_empty());
+    }
+
+    #[test]
+    fn test_cached_engine_keyed() {
+        let engine = CachedEngine::new(CountingEngine::default(), Duration::from_secs(60));
+        assert_eq!(engine.render(Keyed::new("home", 1)).unwrap(), "1");
+        // the context value is ignored when the key matches.
+        assert_eq!(engine.render(Keyed::new("home", 2)).unwrap(), "1");
+        assert_eq!(engine.render(Keyed::new("about", 2)).unwrap(), "2");
+        assert_eq!(engine.engine().count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_cache_key() {
+        assert_eq!(
+            cache_key(&"Alice".to_owned()),
+            cache_key(&"Alice".to_owned())
+        );
+        assert_ne!(cache_key(&"Alice".to_owned()), cache_key(&"Bob".to_owned()));
+        // the values of distinct types never share the key.
+        assert_ne!(cache_key(&1u32), cache_key(&1i32));
+        assert_eq!(
+            cache_key(&Keyed::new("home", 1)),
+            cache_key(&Keyed::new("home", 2))
+        );
+    }
+
+    #[test]
+    fn test_cached_engine_ttl() {
+        let engine = CachedEngine::new(CountingEngine::default(), Duration::from_millis(20));
+        engine.render("Alice".to_owned()).unwrap();
+        thread::sleep(Duration::from_millis(40));
+        engine.render("Alice".to_owned()).unwrap();
+        assert_eq!(engine.engine().count.load(Ordering::SeqCst), 2);
+        assert_eq!(engine.handle().hits(), 0);
+    }
+
+    #[test]
+    fn test_cached_engine_max_entries() {
+        let engine =
+            CachedEngine::new(CountingEngine::default(), Duration::from_secs(60)).max_entries(2);
+        let handle = engine.handle();
+        for name in &["a", "b", "c"] {
+            engine.render(name.to_string()).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(handle.len(), 2);
+        // the oldest entry has been evicted.
+        engine.render("a".to_owned()).unwrap();
+        assert_eq!(handle.hits(), 0);
+        engine.render("c".to_owned()).unwrap();
+        assert_eq!(handle.hits(), 1);
+    }
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "use-cbor")]
+
+use http::header::HeaderValue;
+use serde::Serialize;
+use std::io;
+
+use super::serializer::{Format, SerializerEngine};
+use renderer::Renderer;
+
+/// Create a `Renderer` which serializes the context values into CBOR.
+pub fn cbor() -> Renderer<CborEngine> {
+    Renderer::new(CborEngine::default())
+}
+
+/// A template engine which serializes the context values into CBOR.
+pub type CborEngine = SerializerEngine<Cbor>;
+
+/// The CBOR format.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Cbor {
+    _priv: (),
+}
+
+impl Format for Cbor {
+    type Body = Vec<u8>;
+    type Error = ::ciborium::ser::Error<io::Error>;
+
+    fn content_type(&self) -> HeaderValue {
+        HeaderValue::from_static("application/cbor")
+    }
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Self::Body, Self::Error> {
+        let mut body = vec![];
+        ::ciborium::ser::into_writer(value, &mut body)?;
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cbor, CborEngine};
+    use backend::engine::Engine;
+    use backend::serializer::tests::assert_serialize_error;
+
+    use serde::Serialize;
+
+    #[test]
+    fn test_cbor() {
+        #[derive(Debug, Serialize)]
+        struct Context {
+            name: String,
+        }
+
+        let value = Context {
+            name: "Alice".into(),
+        };
+
+        let engine = CborEngine::default();
+        assert_matches!(
+            engine.content_type_hint(&value),
+            Some(ref h) if h == "application/cbor"
+        );
+        assert_eq!(
+            engine.render(value).unwrap(),
+            b"\xa1\x64name\x65Alice".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_cbor_error() {
+        assert_serialize_error(Cbor::default());
+    }
+}
@@ -0,0 +1,399 @@
+use either::Either;
+use failure;
+use http::header::HeaderValue;
+
+use super::engine::{Engine, HasTemplate, TemplateNotFound, Validate};
+
+/// A template engine which dispatches the context values of `Either<L, R>` to two engines.
+///
+/// The values of `Left` are rendered by the first engine, and the values of `Right`
+/// by the second one. The rendered body is `Either<A::Body, B::Body>`.
+#[derive(Debug)]
+pub struct EitherEngine<A, B> {
+    left: A,
+    right: B,
+}
+
+impl<A, B> EitherEngine<A, B> {
+    /// Create a new `EitherEngine` from the engines for `Left` and `Right`.
+    pub fn new(left: A, right: B) -> EitherEngine<A, B> {
+        EitherEngine { left, right }
+    }
+
+    /// Returns a reference to the engine for `Left`.
+    pub fn left(&self) -> &A {
+        &self.left
+    }
+
+    /// Returns a reference to the engine for `Right`.
+    pub fn right(&self) -> &B {
+        &self.right
+    }
+}
+
+impl<A, B, L, R> Engine<Either<L, R>> for EitherEngine<A, B>
+where
+    A: Engine<L>,
+    B: Engine<R>,
+{
+    type Body = Either<A::Body, B::Body>;
+    type Error = failure::Error;
+
+    fn content_type_hint(&self, value: &Either<L, R>) -> Option<HeaderValue> {
+        match *value {
+            Either::Left(ref value) => self.left.content_type_hint(value),
+            Either::Right(ref value) => self.right.content_type_hint(value),
+        }
+    }
+
+    fn render(&self, value: Either<L, R>) -> Result<Self::Body, Self::Error> {
+        match value {
+            Either::Left(value) => self
+                .left
+                .render(value)
+                .map(Either::Left)
+                .map_err(Into::into),
+            Either::Right(value) => self
+                .right
+                .render(value)
+                .map(Either::Right)
+                .map_err(Into::into),
+        }
+    }
+
+    fn render_with_hint(
+        &self,
+        value: Either<L, R>,
+    ) -> Result<(Option<HeaderValue>, Self::Body), Self::Error> {
+        match value {
+            Either::Left(value) => self
+                .left
+                .render_with_hint(value)
+                .map(|(content_type, body)| (content_type, Either::Left(body)))
+                .map_err(Into::into),
+            Either::Right(value) => self
+                .right
+                .render_with_hint(value)
+                .map(|(content_type, body)| (content_type, Either::Right(body)))
+                .map_err(Into::into),
+        }
+    }
+}
+
+impl<A, B> Validate for EitherEngine<A, B>
+where
+    A: Validate,
+    B: Validate,
+{
+    fn validate(&self) -> Result<(), failure::Error> {
+        self.left.validate()?;
+        self.right.validate()
+    }
+}
+
+/// A template engine which falls back to the second engine when the template
+/// of the first engine is missing.
+///
+/// The context value is moved into the engine at rendering, so the existence of
+/// the template is checked by `HasTemplate` before rendering. `Renderer` checks
+/// it once per request, and uses the content-type of the chosen engine. The
+/// rendered body is `Either<A::Body, B::Body>`.
+///
+/// Note that the rendering fails, rather than falling back, if the template of the
+/// primary engine is removed between the check and the rendering (e.g. by a
+/// reload of `Reloadable`).
+#[derive(Debug)]
+pub struct FallbackEngine<A, B> {
+    primary: A,
+    fallback: B,
+}
+
+impl<A, B> FallbackEngine<A, B> {
+    /// Create a new `FallbackEngine` which tries `primary` first and falls back to `fallback`.
+    pub fn new(primary: A, fallback: B) -> FallbackEngine<A, B> {
+        FallbackEngine { primary, fallback }
+    }
+
+    /// Returns a reference to the engine tried first.
+    pub fn primary(&self) -> &A {
+        &self.primary
+    }
+
+    /// Returns a reference to the engine used when the template of the primary engine is missing.
+    pub fn fallback(&self) -> &B {
+        &self.fallback
+    }
+}
+
+impl<A, B, CtxT> Engine<CtxT> for FallbackEngine<A, B>
+where
+    A: Engine<CtxT> + HasTemplate<CtxT>,
+    B: Engine<CtxT>,
+{
+    type Body = Either<A::Body, B::Body>;
+    type Error = failure::Error;
+
+    fn content_type_hint(&self, value: &CtxT) -> Option<HeaderValue> {
+        if self.primary.has_template(value) {
+            self.primary.content_type_hint(value)
+        } else {
+            self.fallback.content_type_hint(value)
+        }
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        if self.primary.has_template(&value) {
+            self.primary
+                .render(value)
+                .map(Either::Left)
+                .map_err(Into::into)
+        } else {
+            self.fallback
+                .render(value)
+                .map(Either::Right)
+                .map_err(Into::into)
+        }
+    }
+
+    fn render_with_hint(
+        &self,
+        value: CtxT,
+    ) -> Result<(Option<HeaderValue>, Self::Body), Self::Error> {
+        if self.primary.has_template(&value) {
+            self.primary
+                .render_with_hint(value)
+                .map(|(content_type, body)| (content_type, Either::Left(body)))
+                .map_err(Into::into)
+        } else {
+            self.fallback
+                .render_with_hint(value)
+                .map(|(content_type, body)| (content_type, Either::Right(body)))
+                .map_err(Into::into)
+        }
+    }
+}
+
+impl<A, B, CtxT> HasTemplate<CtxT> for FallbackEngine<A, B>
+where
+    A: HasTemplate<CtxT>,
+    B: HasTemplate<CtxT>,
+{
+    fn has_template(&self, value: &CtxT) -> bool {
+        self.primary.has_template(value) || self.fallback.has_template(value)
+    }
+}
+
+impl<A, B> Validate for FallbackEngine<A, B>
+where
+    A: Validate,
+    B: Validate,
+{
+    fn validate(&self) -> Result<(), failure::Error> {
+        // The primary template is allowed to be missing, but it must be valid if registered.
+        match self.primary.validate() {
+            Err(ref err) if err.downcast_ref::<TemplateNotFound>().is_some() => {}
+            result => result?,
+        }
+        self.fallback.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EitherEngine, FallbackEngine};
+    use backend::engine::{Engine, HasTemplate, TemplateNotFound, Validate};
+    use renderer::Renderer;
+
+    use either::Either;
+    use failure;
+    use finchers::error::Never;
+    use finchers::prelude::*;
+    use finchers::test;
+    use http::header::HeaderValue;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// An engine which has the templates of the specified names.
+    #[derive(Debug)]
+    struct DummyEngine {
+        prefix: &'static str,
+        templates: &'static [&'static str],
+    }
+
+    impl Engine<&'static str> for DummyEngine {
+        type Body = String;
+        type Error = failure::Error;
+        fn content_type_hint(&self, _: &&'static str) -> Option<HeaderValue> {
+            Some(HeaderValue::from_static("text/plain; charset=utf-8"))
+        }
+        fn render(&self, name: &'static str) -> Result<Self::Body, Self::Error> {
+            if !self.templates.contains(&name) {
+                return Err(failure::format_err!(
+                    "the template `{}` is not registered",
+                    name
+                ));
+            }
+            Ok(format!("{}:{}", self.prefix, name))
+        }
+    }
+
+    impl HasTemplate<&'static str> for DummyEngine {
+        fn has_template(&self, name: &&'static str) -> bool {
+            self.templates.contains(name)
+        }
+    }
+
+    impl Validate for DummyEngine {
+        fn validate(&self) -> Result<(), failure::Error> {
+            if self.templates.is_empty() {
+                return Err(failure::format_err!("no templates"));
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct LengthEngine;
+    impl Engine<usize> for LengthEngine {
+        type Body = Vec<u8>;
+        type Error = Never;
+        fn render(&self, len: usize) -> Result<Self::Body, Self::Error> {
+            Ok(vec![b'a'; len])
+        }
+    }
+
+    #[test]
+    fn test_either_engine() {
+        let engine = EitherEngine::new(
+            DummyEngine {
+                prefix: "left",
+                templates: &["index"],
+            },
+            LengthEngine,
+        );
+
+        let value: Either<&'static str, usize> = Either::Left("index");
+        assert_matches!(
+            engine.content_type_hint(&value),
+            Some(ref h) if h == "text/plain; charset=utf-8"
+        );
+        assert_matches!(engine.render(value), Ok(Either::Left(ref body)) if body == "left:index");
+
+        let value: Either<&'static str, usize> = Either::Right(3);
+        assert_eq!(engine.content_type_hint(&value), None);
+        assert_matches!(engine.render(value), Ok(Either::Right(ref body)) if body == b"aaa");
+
+        assert!(engine.render(Either::Left("missing")).is_err());
+    }
+
+    #[test]
+    fn test_fallback_engine() {
+        let engine = FallbackEngine::new(
+            DummyEngine {
+                prefix: "themed",
+                templates: &["index"],
+            },
+            DummyEngine {
+                prefix: "default",
+                templates: &["index", "about"],
+            },
+        );
+        assert!(engine.validate().is_ok());
+        assert!(engine.has_template(&"about"));
+        assert!(!engine.has_template(&"missing"));
+
+        let mut runner = test::runner({
+            endpoint::syntax::param::<String>()
+                .map(|name: String| -> &'static str {
+                    match &*name {
+                        "index" => "index",
+                        "about" => "about",
+                        _ => "missing",
+                    }
+                })
+                .wrap(Renderer::new(engine))
+        });
+
+        let response = runner.perform("/index").unwrap();
+        assert_eq!(response.body().to_utf8().unwrap(), "themed:index");
+
+        let response = runner.perform("/about").unwrap();
+        assert_eq!(response.body().to_utf8().unwrap(), "default:about");
+
+        let response = runner.perform("/missing").unwrap();
+        assert_eq!(response.status().as_u16(), 500);
+    }
+
+    #[test]
+    fn test_fallback_engine_checks_once() {
+        #[derive(Debug)]
+        struct CountingEngine {
+            inner: DummyEngine,
+            checks: AtomicUsize,
+        }
+        impl Engine<&'static str> for CountingEngine {
+            type Body = String;
+            type Error = failure::Error;
+            fn content_type_hint(&self, name: &&'static str) -> Option<HeaderValue> {
+                self.inner.content_type_hint(name)
+            }
+            fn render(&self, name: &'static str) -> Result<Self::Body, Self::Error> {
+                self.inner.render(name)
+            }
+        }
+        impl HasTemplate<&'static str> for CountingEngine {
+            fn has_template(&self, name: &&'static str) -> bool {
+                self.checks.fetch_add(1, Ordering::SeqCst);
+                self.inner.has_template(name)
+            }
+        }
+
+        let engine = FallbackEngine::new(
+            CountingEngine {
+                inner: DummyEngine {
+                    prefix: "themed",
+                    templates: &["index"],
+                },
+                checks: AtomicUsize::new(0),
+            },
+            DummyEngine {
+                prefix: "default",
+                templates: &["index", "about"],
+            },
+        );
+        let renderer = Renderer::new(engine);
+
+        let response = renderer.render_response("about").unwrap();
+        assert_matches!(*response.body(), Either::Right(ref body) if body == "default:about");
+        assert_eq!(renderer.engine().primary().checks.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_fallback_engine_validate() {
+        struct InvalidEngine(fn() -> failure::Error);
+        impl Validate for InvalidEngine {
+            fn validate(&self) -> Result<(), failure::Error> {
+                Err((self.0)())
+            }
+        }
+        let fallback = || DummyEngine {
+            prefix: "default",
+            templates: &["index"],
+        };
+
+        // the missing template of the primary engine is allowed.
+        let engine = FallbackEngine::new(
+            InvalidEngine(|| TemplateNotFound::new("index").into()),
+            fallback(),
+        );
+        assert!(engine.validate().is_ok());
+
+        let engine = FallbackEngine::new(
+            InvalidEngine(|| failure::format_err!("broken template")),
+            fallback(),
+        );
+        assert_eq!(
+            engine.validate().unwrap_err().to_string(),
+            "broken template"
+        );
+    }
+}
@@ -0,0 +1,53 @@
+#![cfg(any(feature = "use-tera", feature = "use-handlebars"))]
+
+use super::template_dir::TemplateFile;
+
+/// A set of templates embedded into the binary at compile time.
+///
+/// The value of this type is usually generated by `TemplateDir::embed` in the
+/// build script, and the registry is created at startup by `load_tera` or
+/// `load_handlebars`, without reading any files at runtime.
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedTemplates {
+    templates: &'static [(&'static str, &'static str)],
+}
+
+impl EmbeddedTemplates {
+    /// Create a new `EmbeddedTemplates` from the pairs of template names and their contents.
+    pub const fn new(templates: &'static [(&'static str, &'static str)]) -> EmbeddedTemplates {
+        EmbeddedTemplates { templates }
+    }
+
+    /// Returns the content of the template with the specified name.
+    pub fn get(&self, name: &str) -> Option<&'static str> {
+        self.templates
+            .iter()
+            .find(|&&(n, _)| n == name)
+            .map(|&(_, content)| content)
+    }
+
+    /// Returns an iterator over the pairs of template names and their contents.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        self.templates.iter().cloned()
+    }
+
+    pub(crate) fn to_files(self) -> Vec<TemplateFile> {
+        self.iter()
+            .map(|(name, content)| TemplateFile {
+                name: name.to_owned(),
+                path: None,
+                content: content.to_owned(),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_embedded_templates() {
+    static TEMPLATES: EmbeddedTemplates =
+        EmbeddedTemplates::new(&[("index.html", "index"), ("about.html", "about")]);
+
+    assert_eq!(TEMPLATES.get("about.html"), Some("about"));
+    assert_eq!(TEMPLATES.get("missing.html"), None);
+    assert_eq!(TEMPLATES.iter().count(), 2);
+}
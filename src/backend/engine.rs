@@ -1,18 +1,135 @@
-use failure;
+use failure::{self, Fail};
 use finchers::output::body::ResBody;
 use http::header::HeaderValue;
+use std::fmt;
+use std::io;
 
 /// A trait representing a template engine.
-pub trait Engine<CtxT>: EngineImpl<CtxT> {}
-
-pub trait EngineImpl<CtxT> {
+///
+/// This trait is the extension point for plugging template engines into
+/// `Renderer`. The engines bundled in this crate are implemented on top of it,
+/// and third-party crates may implement it for their own engines as well.
+///
+/// # Example
+///
+/// ```
+/// # extern crate finchers;
+/// # extern crate finchers_template;
+/// # extern crate http;
+/// use finchers::error::Never;
+/// use finchers_template::backend::Engine;
+/// use finchers_template::Renderer;
+/// use http::header::HeaderValue;
+///
+/// #[derive(Debug)]
+/// struct PlainTextEngine;
+///
+/// impl<CtxT: ToString> Engine<CtxT> for PlainTextEngine {
+///     type Body = String;
+///     type Error = Never;
+///
+///     fn content_type_hint(&self, _: &CtxT) -> Option<HeaderValue> {
+///         Some(HeaderValue::from_static("text/plain; charset=utf-8"))
+///     }
+///
+///     fn render(&self, ctx: CtxT) -> Result<Self::Body, Self::Error> {
+///         Ok(ctx.to_string())
+///     }
+/// }
+///
+/// # fn main() {
+/// let renderer = Renderer::new(PlainTextEngine);
+/// # drop(renderer);
+/// # }
+/// ```
+pub trait Engine<CtxT> {
+    /// The type of response body created by this engine.
     type Body: ResBody;
+
+    /// The error type which will be returned from `render`.
     type Error: Into<failure::Error>;
 
+    /// Returns the value of content-type guessed from the given context.
+    ///
+    /// `Renderer` uses this value only when the content-type is not explicitly
+    /// specified, and falls back to `text/html` when it returns `None`.
     #[allow(unused_variables)]
     fn content_type_hint(&self, ctx: &CtxT) -> Option<HeaderValue> {
         None
     }
 
+    /// Renders the given context value into a response body.
     fn render(&self, ctx: CtxT) -> Result<Self::Body, Self::Error>;
+
+    /// Renders the given context value, and returns the body along with the
+    /// value of content-type guessed from it.
+    ///
+    /// `Renderer` calls this method when the content-type is not explicitly
+    /// specified. The default implementation calls `content_type_hint` and
+    /// `render` in turn, and the engines which look up the template in both of
+    /// them (e.g. `FallbackEngine`) override it to look up only once.
+    fn render_with_hint(
+        &self,
+        ctx: CtxT,
+    ) -> Result<(Option<HeaderValue>, Self::Body), Self::Error> {
+        let content_type = self.content_type_hint(&ctx);
+        self.render(ctx).map(|body| (content_type, body))
+    }
+}
+
+/// A trait representing a template engine which can write the rendered output incrementally.
+///
+/// The engines implementing this trait can be used with `Renderer::streaming`,
+/// which sends the rendered output to the client as a chunked response body.
+pub trait StreamingEngine<CtxT>: Engine<CtxT> {
+    /// Renders the given context value into the specified writer.
+    fn render_to_write(&self, ctx: CtxT, writer: &mut dyn io::Write) -> Result<(), Self::Error>;
+}
+
+/// A trait representing a template engine which can check its configuration before serving requests.
+///
+/// The engines implementing this trait can be validated at startup by
+/// `Renderer::validate`, so that the mistakes such as a misspelled template
+/// name are reported before the first request rather than as a `500` response.
+pub trait Validate {
+    /// Checks whether the engine is able to render the configured template.
+    ///
+    /// If the configured template itself is not registered, the returned error
+    /// should be a `TemplateNotFound`.
+    fn validate(&self) -> Result<(), failure::Error>;
+}
+
+/// The error type returned by `Validate` when the configured template is not registered.
+#[derive(Debug)]
+pub struct TemplateNotFound {
+    name: String,
+}
+
+impl TemplateNotFound {
+    /// Create a new `TemplateNotFound` with the name of the missing template.
+    pub fn new(name: impl Into<String>) -> TemplateNotFound {
+        TemplateNotFound { name: name.into() }
+    }
+
+    /// Returns the name of the missing template.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for TemplateNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the template `{}` is not registered", self.name)
+    }
+}
+
+impl Fail for TemplateNotFound {}
+
+/// A trait representing a template engine which can tell whether the template used
+/// for rendering a context value is registered.
+///
+/// This trait is used by `FallbackEngine` to choose the engine before rendering.
+pub trait HasTemplate<CtxT> {
+    /// Returns `true` if the template used for rendering the given context value is registered.
+    fn has_template(&self, ctx: &CtxT) -> bool;
 }
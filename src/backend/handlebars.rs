@@ -1,14 +1,21 @@
 #![cfg(feature = "use-handlebars")]
 
-use super::engine::{Engine, EngineImpl};
+use super::embed::EmbeddedTemplates;
+use super::engine::{Engine, HasTemplate, StreamingEngine, TemplateNotFound, Validate};
+use super::reload::Reloadable;
+use super::template_dir::{TemplateDir, TemplateDirError, TemplateError, TemplateFile};
+use super::template_name::{guess_content_type, TemplateName};
 use renderer::Renderer;
 
-use failure::SyncFailure;
-use handlebars::Handlebars;
+use failure::{self, SyncFailure};
+use handlebars::template::{Parameter, TemplateElement};
+use handlebars::{Handlebars, JsonValue};
 use http::header::HeaderValue;
-use mime_guess::guess_mime_type_opt;
 use serde::Serialize;
 use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
 
 pub trait AsHandlebars {
     fn as_handlebars(&self) -> &Handlebars;
@@ -38,16 +45,102 @@ impl<T: AsHandlebars> AsHandlebars for ::std::sync::Arc<T> {
     }
 }
 
+/// A trait representing a source of `Handlebars` which is accessed only within a closure.
+///
+/// This trait is implemented for all types implementing `AsHandlebars`, and for
+/// `Reloadable<Handlebars>` whose registry may be replaced between two renderings.
+pub trait WithHandlebars {
+    fn with_handlebars<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Handlebars) -> R;
+}
+
+impl<H: AsHandlebars> WithHandlebars for H {
+    fn with_handlebars<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Handlebars) -> R,
+    {
+        f(self.as_handlebars())
+    }
+}
+
+impl WithHandlebars for Reloadable<Handlebars> {
+    fn with_handlebars<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Handlebars) -> R,
+    {
+        f(&self.registry())
+    }
+}
+
+impl WithHandlebars for ::std::sync::Arc<Reloadable<Handlebars>> {
+    fn with_handlebars<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Handlebars) -> R,
+    {
+        (**self).with_handlebars(f)
+    }
+}
+
+impl Reloadable<Handlebars> {
+    /// Create a `Reloadable<Handlebars>` which registers all files under `dir` by their relative paths.
+    pub fn handlebars(dir: impl Into<PathBuf>) -> Result<Reloadable<Handlebars>, failure::Error> {
+        Reloadable::new(dir, load_handlebars_dir)
+    }
+}
+
+fn load_handlebars_dir(dir: &Path) -> Result<Handlebars, failure::Error> {
+    Ok(TemplateDir::new(dir).load_handlebars()?)
+}
+
+impl TemplateDir {
+    /// Creates a `Handlebars` which registers all templates in the directory.
+    pub fn load_handlebars(&self) -> Result<Handlebars, TemplateDirError> {
+        load_handlebars_templates(self.read_templates()?)
+    }
+}
+
+impl EmbeddedTemplates {
+    /// Creates a `Handlebars` which registers all embedded templates.
+    pub fn load_handlebars(&self) -> Result<Handlebars, TemplateDirError> {
+        load_handlebars_templates(self.to_files())
+    }
+}
+
+pub(crate) fn load_handlebars_templates(
+    templates: Vec<TemplateFile>,
+) -> Result<Handlebars, TemplateDirError> {
+    let mut registry = Handlebars::new();
+    let mut errors = vec![];
+    for t in templates {
+        if let Err(err) = registry.register_template_string(&t.name, &t.content) {
+            errors.push(TemplateError::new(Some(t.name), t.path, err.to_string()));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(TemplateDirError::templates(errors));
+    }
+    Ok(registry)
+}
+
 pub fn handlebars<H>(
     registry: H,
     name: impl Into<Cow<'static, str>>,
 ) -> Renderer<HandlebarsEngine<H>>
 where
-    H: AsHandlebars,
+    H: WithHandlebars,
 {
     Renderer::new(HandlebarsEngine::new(registry, name))
 }
 
+/// Create a `Renderer` which selects the template by the name provided from the context value.
+pub fn handlebars_dynamic<H>(registry: H) -> Renderer<DynamicHandlebarsEngine<H>>
+where
+    H: WithHandlebars,
+{
+    Renderer::new(DynamicHandlebarsEngine::new(registry))
+}
+
 #[derive(Debug)]
 pub struct HandlebarsEngine<H> {
     registry: H,
@@ -57,12 +150,11 @@ pub struct HandlebarsEngine<H> {
 
 impl<H> HandlebarsEngine<H>
 where
-    H: AsHandlebars,
+    H: WithHandlebars,
 {
     pub fn new(registry: H, name: impl Into<Cow<'static, str>>) -> HandlebarsEngine<H> {
         let name = name.into();
-        let content_type = guess_mime_type_opt(&*name)
-            .map(|s| s.as_ref().parse().expect("should be a valid header value"));
+        let content_type = guess_content_type(&name);
         HandlebarsEngine {
             registry,
             name,
@@ -72,19 +164,15 @@ where
 
     pub fn set_template_name(&mut self, name: impl Into<Cow<'static, str>>) {
         self.name = name.into();
-        if let Some(value) = guess_mime_type_opt(&*self.name)
-            .map(|s| s.as_ref().parse().expect("should be a valid header name"))
-        {
+        if let Some(value) = guess_content_type(&self.name) {
             self.content_type = Some(value);
         }
     }
 }
 
-impl<H, T: Serialize> Engine<T> for HandlebarsEngine<H> where H: AsHandlebars {}
-
-impl<H, CtxT: Serialize> EngineImpl<CtxT> for HandlebarsEngine<H>
+impl<H, CtxT: Serialize> Engine<CtxT> for HandlebarsEngine<H>
 where
-    H: AsHandlebars,
+    H: WithHandlebars,
 {
     type Body = String;
     type Error = SyncFailure<::handlebars::RenderError>;
@@ -95,8 +183,226 @@ where
 
     fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
         self.registry
-            .as_handlebars()
-            .render(&self.name, &value)
+            .with_handlebars(|registry| registry.render(&self.name, &value))
+            .map_err(SyncFailure::new)
+    }
+}
+
+impl<H> Validate for HandlebarsEngine<H>
+where
+    H: WithHandlebars,
+{
+    fn validate(&self) -> Result<(), failure::Error> {
+        self.registry.with_handlebars(|registry| {
+            let inlines = collect_inline_partials(registry);
+            let mut visited = HashSet::new();
+            validate_template(registry, &self.name, None, &inlines, &mut visited)
+        })
+    }
+}
+
+impl<H, CtxT> HasTemplate<CtxT> for HandlebarsEngine<H>
+where
+    H: WithHandlebars,
+{
+    fn has_template(&self, _: &CtxT) -> bool {
+        self.registry
+            .with_handlebars(|registry| registry.has_template(&self.name))
+    }
+}
+
+impl<H, CtxT: Serialize> StreamingEngine<CtxT> for HandlebarsEngine<H>
+where
+    H: WithHandlebars,
+{
+    fn render_to_write(&self, value: CtxT, writer: &mut dyn io::Write) -> Result<(), Self::Error> {
+        self.registry
+            .with_handlebars(|registry| registry.render_to_write(&self.name, &value, writer))
+            .map_err(SyncFailure::new)
+    }
+}
+
+/// Checks that the template and all partials referenced from it are registered.
+///
+/// The partials whose names are computed at rendering, the partial blocks (which
+/// have the default content) and the inline partials are not checked.
+fn validate_template<'a>(
+    registry: &'a Handlebars,
+    name: &'a str,
+    referrer: Option<&str>,
+    inlines: &HashSet<&str>,
+    visited: &mut HashSet<&'a str>,
+) -> Result<(), failure::Error> {
+    if !visited.insert(name) {
+        return Ok(());
+    }
+    let template = registry.get_template(name).ok_or_else(|| match referrer {
+        Some(referrer) => failure::format_err!(
+            "the partial `{}` referenced from `{}` is not registered",
+            name,
+            referrer
+        ),
+        None => TemplateNotFound::new(name).into(),
+    })?;
+
+    let mut partials = vec![];
+    collect_partials(&template.elements, &mut partials);
+    for partial in partials {
+        if !partial.starts_with('@') && !inlines.contains(partial) {
+            validate_template(registry, partial, Some(name), inlines, visited)?;
+        }
+    }
+    Ok(())
+}
+
+fn collect_partials<'a>(elements: &'a [TemplateElement], partials: &mut Vec<&'a str>) {
+    for element in elements {
+        match *element {
+            TemplateElement::PartialExpression(ref partial) => {
+                partials.extend(static_name(&partial.name));
+            }
+            TemplateElement::PartialBlock(ref partial) => {
+                if let Some(ref template) = partial.template {
+                    collect_partials(&template.elements, partials);
+                }
+            }
+            TemplateElement::HelperBlock(ref helper) => {
+                for template in helper.template.iter().chain(helper.inverse.iter()) {
+                    collect_partials(&template.elements, partials);
+                }
+            }
+            TemplateElement::DirectiveBlock(ref directive) => {
+                if let Some(ref template) = directive.template {
+                    collect_partials(&template.elements, partials);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Collects the names of the inline partials (`{{#*inline "name"}}`) defined in the registry.
+///
+/// The inline partials are visible from the partials rendered inside their
+/// definitions (e.g. the layouts), so the names are collected from all templates.
+fn collect_inline_partials(registry: &Handlebars) -> HashSet<&str> {
+    fn collect<'a>(elements: &'a [TemplateElement], inlines: &mut HashSet<&'a str>) {
+        for element in elements {
+            match *element {
+                TemplateElement::DirectiveExpression(ref directive)
+                | TemplateElement::DirectiveBlock(ref directive) => {
+                    if static_name(&directive.name) == Some("inline") {
+                        inlines.extend(directive.params.first().and_then(static_name));
+                    }
+                    if let Some(ref template) = directive.template {
+                        collect(&template.elements, inlines);
+                    }
+                }
+                TemplateElement::PartialBlock(ref partial) => {
+                    if let Some(ref template) = partial.template {
+                        collect(&template.elements, inlines);
+                    }
+                }
+                TemplateElement::HelperBlock(ref helper) => {
+                    for template in helper.template.iter().chain(helper.inverse.iter()) {
+                        collect(&template.elements, inlines);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut inlines = HashSet::new();
+    for template in registry.get_templates().values() {
+        collect(&template.elements, &mut inlines);
+    }
+    inlines
+}
+
+/// Returns the name written in the template, or `None` if it is computed at rendering.
+fn static_name(param: &Parameter) -> Option<&str> {
+    match *param {
+        Parameter::Name(ref name) => Some(name),
+        Parameter::Literal(JsonValue::String(ref name)) => Some(name),
+        _ => None,
+    }
+}
+
+/// A template engine which renders the template chosen by the context value.
+#[derive(Debug)]
+pub struct DynamicHandlebarsEngine<H> {
+    registry: H,
+}
+
+impl<H> DynamicHandlebarsEngine<H>
+where
+    H: WithHandlebars,
+{
+    pub fn new(registry: H) -> DynamicHandlebarsEngine<H> {
+        DynamicHandlebarsEngine { registry }
+    }
+}
+
+impl<H, CtxT> Engine<CtxT> for DynamicHandlebarsEngine<H>
+where
+    H: WithHandlebars,
+    CtxT: Serialize + TemplateName,
+{
+    type Body = String;
+    type Error = SyncFailure<::handlebars::RenderError>;
+
+    fn content_type_hint(&self, value: &CtxT) -> Option<HeaderValue> {
+        guess_content_type(value.template_name())
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        self.registry
+            .with_handlebars(|registry| registry.render(value.template_name(), &value))
+            .map_err(SyncFailure::new)
+    }
+}
+
+impl<H> Validate for DynamicHandlebarsEngine<H>
+where
+    H: WithHandlebars,
+{
+    /// Checks that all partials referenced from the registered templates are registered.
+    fn validate(&self) -> Result<(), failure::Error> {
+        self.registry.with_handlebars(|registry| {
+            let inlines = collect_inline_partials(registry);
+            let mut visited = HashSet::new();
+            let mut names: Vec<_> = registry.get_templates().keys().collect();
+            names.sort();
+            for name in names {
+                validate_template(registry, name, None, &inlines, &mut visited)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<H, CtxT> HasTemplate<CtxT> for DynamicHandlebarsEngine<H>
+where
+    H: WithHandlebars,
+    CtxT: TemplateName,
+{
+    fn has_template(&self, value: &CtxT) -> bool {
+        self.registry
+            .with_handlebars(|registry| registry.has_template(value.template_name()))
+    }
+}
+
+impl<H, CtxT> StreamingEngine<CtxT> for DynamicHandlebarsEngine<H>
+where
+    H: WithHandlebars,
+    CtxT: Serialize + TemplateName,
+{
+    fn render_to_write(&self, value: CtxT, writer: &mut dyn io::Write) -> Result<(), Self::Error> {
+        self.registry
+            .with_handlebars(|registry| {
+                registry.render_to_write(value.template_name(), &value, writer)
+            })
             .map_err(SyncFailure::new)
     }
 }
@@ -120,4 +426,185 @@ fn test_handlebars() {
     let engine = HandlebarsEngine::new(registry, "index.html");
     let body = engine.render(value).unwrap();
     assert_eq!(body, "Alice");
+
+    let value = Context { name: "Bob".into() };
+    let mut body = vec![];
+    engine.render_to_write(value, &mut body).unwrap();
+    assert_eq!(body, b"Bob");
+}
+
+#[test]
+fn test_handlebars_dynamic() {
+    use super::template_name::Named;
+
+    #[derive(Debug, Serialize)]
+    struct Context {
+        name: String,
+    }
+
+    let mut registry = Handlebars::new();
+    registry
+        .register_template_string("pages/about.html", "About {{ name }}")
+        .unwrap();
+    registry
+        .register_template_string("pages/robots.txt", "Disallow: {{ name }}")
+        .unwrap();
+
+    let engine = DynamicHandlebarsEngine::new(registry);
+
+    let value = Named::new(
+        format!("pages/{}.html", "about"),
+        Context {
+            name: "Alice".into(),
+        },
+    );
+    assert_matches!(
+        engine.content_type_hint(&value),
+        Some(ref h) if h == "text/html"
+    );
+    assert_eq!(engine.render(value).unwrap(), "About Alice");
+
+    let value = Named::new(
+        "pages/robots.txt",
+        Context {
+            name: "/admin".into(),
+        },
+    );
+    assert_matches!(
+        engine.content_type_hint(&value),
+        Some(ref h) if h == "text/plain"
+    );
+    assert_eq!(engine.render(value).unwrap(), "Disallow: /admin");
+}
+
+#[test]
+fn test_handlebars_reloadable() {
+    use super::reload::tests::temp_dir;
+    use std::fs;
+    use std::time::Duration;
+
+    #[derive(Debug, Serialize)]
+    struct Context {
+        name: String,
+    }
+
+    let dir = temp_dir("handlebars-reloadable");
+    fs::write(dir.join("index.html"), "Hello, {{ name }}.").unwrap();
+
+    let registry = Reloadable::handlebars(&dir)
+        .unwrap()
+        .poll_interval(Duration::from_millis(0));
+    let engine = HandlebarsEngine::new(registry, "index.html");
+    let render = || {
+        engine
+            .render(Context {
+                name: "Alice".into(),
+            })
+            .unwrap()
+    };
+    assert_eq!(render(), "Hello, Alice.");
+
+    fs::remove_file(dir.join("index.html")).unwrap();
+    fs::create_dir_all(dir.join("partials")).unwrap();
+    fs::write(dir.join("partials/name.hbs"), "<{{ name }}>").unwrap();
+    fs::write(dir.join("index.html"), "Bye, {{> partials/name.hbs }}.").unwrap();
+    assert_eq!(render(), "Bye, <Alice>.");
+    assert!(engine.registry.take_error().is_none());
+
+    // a broken template is reported, and the last good version is kept.
+    fs::write(dir.join("broken.html"), "{{#if}}").unwrap();
+    assert_eq!(render(), "Bye, <Alice>.");
+    assert!(engine.registry.take_error().is_some());
+    assert!(engine.registry.reload().is_err());
+}
+
+#[test]
+fn test_handlebars_template_dir() {
+    use super::reload::tests::temp_dir;
+    use std::fs;
+
+    let dir = temp_dir("handlebars-template-dir");
+    fs::create_dir_all(dir.join("partials")).unwrap();
+    fs::write(dir.join("partials/name.hbs"), "<b>{{ name }}</b>").unwrap();
+    fs::write(dir.join("index.hbs"), "Hello, {{> partials/name.hbs }}.").unwrap();
+    fs::write(dir.join("notes.txt"), "{{#if}}").unwrap();
+
+    let dir = TemplateDir::new(&dir).extension("hbs");
+    let registry = dir.load_handlebars().unwrap();
+    let body = registry
+        .render("index.hbs", &::serde_json::json!({ "name": "Alice" }))
+        .unwrap();
+    assert_eq!(body, "Hello, <b>Alice</b>.");
+
+    fs::write(dir.dir().join("broken.hbs"), "{{#if}}").unwrap();
+    let err = dir.load_handlebars().unwrap_err();
+    assert_eq!(err.errors().len(), 1);
+    assert_eq!(err.errors()[0].name(), Some("broken.hbs"));
+}
+
+#[test]
+fn test_handlebars_embedded() {
+    static TEMPLATES: EmbeddedTemplates = EmbeddedTemplates::new(&[
+        ("name.hbs", "<b>{{ name }}</b>"),
+        ("index.hbs", "Hello, {{> name.hbs }}."),
+    ]);
+    let registry = TEMPLATES.load_handlebars().unwrap();
+    let body = registry
+        .render("index.hbs", &::serde_json::json!({ "name": "Alice" }))
+        .unwrap();
+    assert_eq!(body, "Hello, <b>Alice</b>.");
+}
+
+#[test]
+fn test_handlebars_validate() {
+    let mut registry = Handlebars::new();
+    registry
+        .register_template_string("index.html", "{{ name }}")
+        .unwrap();
+    let registry = ::std::sync::Arc::new(registry);
+
+    assert!(handlebars(registry.clone(), "index.html")
+        .validate()
+        .is_ok());
+    let err = handlebars(registry.clone(), "about.html")
+        .validated()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "the template `about.html` is not registered"
+    );
+
+    let mut registry = Handlebars::new();
+    registry
+        .register_template_string("layout.html", "{{> header}}{{> content}}")
+        .unwrap();
+    registry
+        .register_template_string(
+            "index.html",
+            "{{#> layout.html}}{{#*inline \"content\"}}{{> nav}}{{/inline}}{{/layout.html}}",
+        )
+        .unwrap();
+    registry
+        .register_template_string("header", "{{#> title}}Title{{/title}}")
+        .unwrap();
+    registry
+        .register_template_string("nav", "{{#if a}}{{> missing}}{{/if}}")
+        .unwrap();
+    let registry = ::std::sync::Arc::new(registry);
+
+    assert!(handlebars(registry.clone(), "layout.html")
+        .validate()
+        .is_ok());
+    let err = handlebars(registry.clone(), "index.html")
+        .validate()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "the partial `missing` referenced from `nav` is not registered"
+    );
+    let err = handlebars_dynamic(registry.clone()).validate().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "the partial `missing` referenced from `nav` is not registered"
+    );
 }
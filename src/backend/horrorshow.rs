@@ -1,8 +1,9 @@
 #![cfg(feature = "use-horrorshow")]
 
 use horrorshow::Template;
+use std::io;
 
-use super::engine::{Engine, EngineImpl};
+use super::engine::{Engine, StreamingEngine};
 use renderer::Renderer;
 
 pub fn horrorshow() -> Renderer<HorrorshowEngine> {
@@ -14,9 +15,7 @@ pub struct HorrorshowEngine {
     _priv: (),
 }
 
-impl<CtxT: Template> Engine<CtxT> for HorrorshowEngine {}
-
-impl<CtxT: Template> EngineImpl<CtxT> for HorrorshowEngine {
+impl<CtxT: Template> Engine<CtxT> for HorrorshowEngine {
     type Body = String;
     type Error = ::horrorshow::Error;
 
@@ -25,6 +24,12 @@ impl<CtxT: Template> EngineImpl<CtxT> for HorrorshowEngine {
     }
 }
 
+impl<CtxT: Template> StreamingEngine<CtxT> for HorrorshowEngine {
+    fn render_to_write(&self, value: CtxT, writer: &mut dyn io::Write) -> Result<(), Self::Error> {
+        value.write_to_io(writer)
+    }
+}
+
 #[test]
 fn test_horrorshow() {
     let value = {
@@ -37,3 +42,17 @@ fn test_horrorshow() {
     let body = engine.render(value).unwrap();
     assert_eq!(body, "<p>Alice</p>");
 }
+
+#[test]
+fn test_horrorshow_streaming() {
+    let value = {
+        html! {
+            p: "Alice";
+        }
+    };
+
+    let engine = HorrorshowEngine::default();
+    let mut body = vec![];
+    engine.render_to_write(value, &mut body).unwrap();
+    assert_eq!(body, b"<p>Alice</p>");
+}
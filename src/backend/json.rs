@@ -0,0 +1,93 @@
+#![cfg(feature = "use-json")]
+
+use http::header::HeaderValue;
+use serde::Serialize;
+
+use super::serializer::{Format, SerializerEngine};
+use renderer::Renderer;
+
+/// Create a `Renderer` which serializes the context values into compact JSON.
+pub fn json() -> Renderer<JsonEngine> {
+    Renderer::new(JsonEngine::new())
+}
+
+/// Create a `Renderer` which serializes the context values into pretty-printed JSON.
+pub fn json_pretty() -> Renderer<JsonEngine> {
+    Renderer::new(JsonEngine::new().pretty(true))
+}
+
+/// A template engine which serializes the context values into JSON.
+pub type JsonEngine = SerializerEngine<Json>;
+
+impl SerializerEngine<Json> {
+    /// Create a new `JsonEngine` which outputs compact JSON.
+    pub fn new() -> JsonEngine {
+        JsonEngine::default()
+    }
+
+    /// Sets whether the output is pretty-printed.
+    pub fn pretty(self, pretty: bool) -> JsonEngine {
+        JsonEngine::with_format(Json { pretty })
+    }
+}
+
+/// The JSON format.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Json {
+    pretty: bool,
+}
+
+impl Format for Json {
+    type Body = String;
+    type Error = ::serde_json::Error;
+
+    fn content_type(&self) -> HeaderValue {
+        HeaderValue::from_static("application/json")
+    }
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Self::Body, Self::Error> {
+        if self.pretty {
+            ::serde_json::to_string_pretty(value)
+        } else {
+            ::serde_json::to_string(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Json, JsonEngine};
+    use backend::engine::Engine;
+    use backend::serializer::tests::assert_serialize_error;
+
+    use serde::Serialize;
+
+    #[test]
+    fn test_json() {
+        #[derive(Debug, Serialize)]
+        struct Context {
+            name: String,
+        }
+
+        let engine = JsonEngine::new();
+        let value = Context {
+            name: "Alice".into(),
+        };
+        assert_matches!(
+            engine.content_type_hint(&value),
+            Some(ref h) if h == "application/json"
+        );
+        assert_eq!(engine.render(value).unwrap(), r#"{"name":"Alice"}"#);
+
+        let engine = JsonEngine::new().pretty(true);
+        let value = Context {
+            name: "Alice".into(),
+        };
+        assert_eq!(engine.render(value).unwrap(), "{\n  \"name\": \"Alice\"\n}");
+    }
+
+    #[test]
+    fn test_json_error() {
+        assert_serialize_error(Json::default());
+    }
+}
@@ -0,0 +1,372 @@
+#![cfg(feature = "use-liquid")]
+
+use failure;
+use http::header::HeaderValue;
+use liquid::partials::PartialCompiler;
+use liquid::{Parser, ParserBuilder, Template};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::engine::{Engine, HasTemplate, TemplateNotFound, Validate};
+use super::template_name::{guess_content_type, TemplateName};
+use renderer::Renderer;
+
+/// A trait representing a source of parsed Liquid templates.
+pub trait AsLiquid {
+    /// Returns the template registered with the specified name, or `None` if it is missing.
+    fn get_template(&self, name: &str) -> Option<&Template>;
+}
+
+/// A single `Template` is returned regardless of the requested name.
+impl AsLiquid for Template {
+    fn get_template(&self, _: &str) -> Option<&Template> {
+        Some(self)
+    }
+}
+
+impl AsLiquid for LiquidTemplates {
+    fn get_template(&self, name: &str) -> Option<&Template> {
+        self.get(name)
+    }
+}
+
+impl<T: AsLiquid> AsLiquid for Box<T> {
+    fn get_template(&self, name: &str) -> Option<&Template> {
+        (**self).get_template(name)
+    }
+}
+
+impl<T: AsLiquid> AsLiquid for ::std::rc::Rc<T> {
+    fn get_template(&self, name: &str) -> Option<&Template> {
+        (**self).get_template(name)
+    }
+}
+
+impl<T: AsLiquid> AsLiquid for ::std::sync::Arc<T> {
+    fn get_template(&self, name: &str) -> Option<&Template> {
+        (**self).get_template(name)
+    }
+}
+
+/// A set of Liquid templates keyed by their names.
+///
+/// The templates are parsed by the `Parser` given at construction, so the
+/// partials (the templates referenced by `{% include %}`) and the custom tags
+/// and filters are configured on it.
+pub struct LiquidTemplates {
+    parser: Parser,
+    templates: HashMap<String, Template>,
+}
+
+impl fmt::Debug for LiquidTemplates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LiquidTemplates")
+            .field("templates", &self.templates.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Default for LiquidTemplates {
+    fn default() -> LiquidTemplates {
+        LiquidTemplates::new()
+    }
+}
+
+impl LiquidTemplates {
+    /// Create an empty `LiquidTemplates` whose parser supports the standard library of Liquid.
+    pub fn new() -> LiquidTemplates {
+        let parser = ParserBuilder::with_stdlib()
+            .build()
+            .expect("the parser without partials should be built");
+        LiquidTemplates::with_parser(parser)
+    }
+
+    /// Create an empty `LiquidTemplates` which parses the templates by the specified parser.
+    pub fn with_parser(parser: Parser) -> LiquidTemplates {
+        LiquidTemplates {
+            parser,
+            templates: HashMap::new(),
+        }
+    }
+
+    /// Create an empty `LiquidTemplates` which resolves the partials from the specified source.
+    pub fn with_partials<P>(partials: P) -> Result<LiquidTemplates, liquid::Error>
+    where
+        P: PartialCompiler,
+    {
+        let parser = ParserBuilder::with_stdlib().partials(partials).build()?;
+        Ok(LiquidTemplates::with_parser(parser))
+    }
+
+    /// Parses the template source and registers it with the specified name.
+    pub fn add_template(
+        &mut self,
+        name: impl Into<String>,
+        source: &str,
+    ) -> Result<(), liquid::Error> {
+        let template = self.parser.parse(source)?;
+        self.templates.insert(name.into(), template);
+        Ok(())
+    }
+
+    /// Registers a parsed template with the specified name.
+    pub fn insert(&mut self, name: impl Into<String>, template: Template) {
+        self.templates.insert(name.into(), template);
+    }
+
+    /// Returns the template registered with the specified name.
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+
+    /// Returns a reference to the parser.
+    pub fn parser(&self) -> &Parser {
+        &self.parser
+    }
+}
+
+pub fn liquid<L>(templates: L, name: impl Into<Cow<'static, str>>) -> Renderer<LiquidEngine<L>>
+where
+    L: AsLiquid,
+{
+    Renderer::new(LiquidEngine::new(templates, name))
+}
+
+/// Create a `Renderer` which selects the template by the name provided from the context value.
+pub fn liquid_dynamic<L>(templates: L) -> Renderer<DynamicLiquidEngine<L>>
+where
+    L: AsLiquid,
+{
+    Renderer::new(DynamicLiquidEngine::new(templates))
+}
+
+pub struct LiquidEngine<L> {
+    templates: L,
+    name: Cow<'static, str>,
+    content_type: Option<HeaderValue>,
+}
+
+impl<L> fmt::Debug for LiquidEngine<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LiquidEngine")
+            .field("name", &self.name)
+            .field("content_type", &self.content_type)
+            .finish()
+    }
+}
+
+impl<L> LiquidEngine<L>
+where
+    L: AsLiquid,
+{
+    pub fn new(templates: L, name: impl Into<Cow<'static, str>>) -> LiquidEngine<L> {
+        let name = name.into();
+        let content_type = guess_content_type(&name);
+        LiquidEngine {
+            templates,
+            name,
+            content_type,
+        }
+    }
+}
+
+impl<L, CtxT: Serialize> Engine<CtxT> for LiquidEngine<L>
+where
+    L: AsLiquid,
+{
+    type Body = String;
+    type Error = failure::Error;
+
+    fn content_type_hint(&self, _: &CtxT) -> Option<HeaderValue> {
+        self.content_type.clone()
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        render_liquid(&self.templates, &self.name, &value)
+    }
+}
+
+impl<L> Validate for LiquidEngine<L>
+where
+    L: AsLiquid,
+{
+    fn validate(&self) -> Result<(), failure::Error> {
+        get_template(&self.templates, &self.name).map(|_| ())
+    }
+}
+
+impl<L, CtxT> HasTemplate<CtxT> for LiquidEngine<L>
+where
+    L: AsLiquid,
+{
+    fn has_template(&self, _: &CtxT) -> bool {
+        self.templates.get_template(&self.name).is_some()
+    }
+}
+
+/// A template engine which renders the template chosen by the context value.
+pub struct DynamicLiquidEngine<L> {
+    templates: L,
+}
+
+impl<L> fmt::Debug for DynamicLiquidEngine<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynamicLiquidEngine").finish()
+    }
+}
+
+impl<L> DynamicLiquidEngine<L>
+where
+    L: AsLiquid,
+{
+    pub fn new(templates: L) -> DynamicLiquidEngine<L> {
+        DynamicLiquidEngine { templates }
+    }
+}
+
+impl<L, CtxT> Engine<CtxT> for DynamicLiquidEngine<L>
+where
+    L: AsLiquid,
+    CtxT: Serialize + TemplateName,
+{
+    type Body = String;
+    type Error = failure::Error;
+
+    fn content_type_hint(&self, value: &CtxT) -> Option<HeaderValue> {
+        guess_content_type(value.template_name())
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        render_liquid(&self.templates, value.template_name(), &value)
+    }
+}
+
+impl<L, CtxT> HasTemplate<CtxT> for DynamicLiquidEngine<L>
+where
+    L: AsLiquid,
+    CtxT: TemplateName,
+{
+    fn has_template(&self, value: &CtxT) -> bool {
+        self.templates.get_template(value.template_name()).is_some()
+    }
+}
+
+fn get_template<'a, L: AsLiquid>(
+    templates: &'a L,
+    name: &str,
+) -> Result<&'a Template, failure::Error> {
+    templates
+        .get_template(name)
+        .ok_or_else(|| TemplateNotFound::new(name).into())
+}
+
+fn render_liquid<L, CtxT>(templates: &L, name: &str, value: &CtxT) -> Result<String, failure::Error>
+where
+    L: AsLiquid,
+    CtxT: Serialize,
+{
+    let template = get_template(templates, name)?;
+    let globals = liquid::to_object(value)?;
+    Ok(template.render(&globals)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DynamicLiquidEngine, LiquidEngine, LiquidTemplates};
+    use backend::engine::{Engine, TemplateNotFound, Validate};
+    use backend::template_name::Named;
+    use liquid::partials::{EagerCompiler, InMemorySource};
+    use liquid::ParserBuilder;
+    use serde::Serialize;
+    use std::sync::Arc;
+
+    #[derive(Debug, Serialize)]
+    struct Context {
+        name: String,
+    }
+
+    #[test]
+    fn test_liquid() {
+        let template = ParserBuilder::with_stdlib()
+            .build()
+            .unwrap()
+            .parse("Hello, {{ name | upcase }}.")
+            .unwrap();
+
+        let engine = LiquidEngine::new(template, "index.html");
+        let value = Context {
+            name: "Alice".into(),
+        };
+        assert_matches!(
+            engine.content_type_hint(&value),
+            Some(ref h) if h == "text/html"
+        );
+        assert_eq!(engine.render(value).unwrap(), "Hello, ALICE.");
+    }
+
+    #[test]
+    fn test_liquid_partials() {
+        let mut source = InMemorySource::new();
+        source.add("greeting", "Hello, {{ name }}");
+
+        let mut templates = LiquidTemplates::with_partials(EagerCompiler::new(source)).unwrap();
+        templates
+            .add_template("index.html", "{% include 'greeting' %}!")
+            .unwrap();
+
+        let engine = LiquidEngine::new(Arc::new(templates), "index.html");
+        let value = Context {
+            name: "Alice".into(),
+        };
+        assert_eq!(engine.render(value).unwrap(), "Hello, Alice!");
+    }
+
+    #[test]
+    fn test_liquid_dynamic() {
+        let mut templates = LiquidTemplates::new();
+        templates
+            .add_template("mail.txt", "Dear {{ name }}")
+            .unwrap();
+
+        let engine = DynamicLiquidEngine::new(templates);
+        let value = Named::new(
+            "mail.txt",
+            Context {
+                name: "Alice".into(),
+            },
+        );
+        assert_matches!(
+            engine.content_type_hint(&value),
+            Some(ref h) if h == "text/plain"
+        );
+        assert_eq!(engine.render(value).unwrap(), "Dear Alice");
+
+        let value = Named::new("missing.html", Context { name: "Bob".into() });
+        let err = engine.render(value).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the template `missing.html` is not registered"
+        );
+    }
+
+    #[test]
+    fn test_liquid_validate() {
+        let mut templates = LiquidTemplates::new();
+        templates.add_template("index.html", "{{ name }}").unwrap();
+        assert!(templates.add_template("broken.html", "{% if %}").is_err());
+
+        let templates = Arc::new(templates);
+        assert!(LiquidEngine::new(templates.clone(), "index.html")
+            .validate()
+            .is_ok());
+        let err = LiquidEngine::new(templates.clone(), "broken.html")
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TemplateNotFound>().unwrap().name(),
+            "broken.html"
+        );
+    }
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "use-maud")]
+
+use finchers::error::Never;
+#[cfg(test)]
+use maud::html;
+use maud::Render;
+
+use super::engine::Engine;
+use renderer::Renderer;
+
+pub fn maud() -> Renderer<MaudEngine> {
+    Renderer::new(MaudEngine::default())
+}
+
+#[derive(Debug, Default)]
+pub struct MaudEngine {
+    _priv: (),
+}
+
+impl<CtxT: Render> Engine<CtxT> for MaudEngine {
+    type Body = String;
+    type Error = Never;
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        Ok(value.render().into_string())
+    }
+}
+
+#[test]
+fn test_maud() {
+    let value = html! {
+        p { "Alice" }
+    };
+
+    let engine = MaudEngine::default();
+    let body = engine.render(value).unwrap();
+    assert_eq!(body, "<p>Alice</p>");
+}
+
+#[test]
+fn test_maud_render() {
+    struct User<'a> {
+        name: &'a str,
+    }
+
+    impl<'a> Render for User<'a> {
+        fn render_to(&self, buffer: &mut String) {
+            html! { p.user { (self.name) } }.render_to(buffer)
+        }
+    }
+
+    let engine = MaudEngine::default();
+    let body = engine.render(User { name: "<Alice>" }).unwrap();
+    assert_eq!(body, "<p class=\"user\">&lt;Alice&gt;</p>");
+}
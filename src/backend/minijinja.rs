@@ -0,0 +1,267 @@
+#![cfg(feature = "use-minijinja")]
+
+use failure;
+use http::header::HeaderValue;
+use minijinja::{Environment, ErrorKind};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::error::Error as StdError;
+
+use super::engine::{Engine, HasTemplate, TemplateNotFound, Validate};
+use super::template_name::{guess_content_type, TemplateName};
+use renderer::Renderer;
+
+pub trait AsEnvironment {
+    fn as_environment(&self) -> &Environment<'static>;
+}
+
+impl AsEnvironment for Environment<'static> {
+    fn as_environment(&self) -> &Environment<'static> {
+        self
+    }
+}
+
+impl<E: AsEnvironment> AsEnvironment for Box<E> {
+    fn as_environment(&self) -> &Environment<'static> {
+        (**self).as_environment()
+    }
+}
+
+impl<E: AsEnvironment> AsEnvironment for ::std::rc::Rc<E> {
+    fn as_environment(&self) -> &Environment<'static> {
+        (**self).as_environment()
+    }
+}
+
+impl<E: AsEnvironment> AsEnvironment for ::std::sync::Arc<E> {
+    fn as_environment(&self) -> &Environment<'static> {
+        (**self).as_environment()
+    }
+}
+
+pub fn minijinja<E>(env: E, name: impl Into<Cow<'static, str>>) -> Renderer<MiniJinjaEngine<E>>
+where
+    E: AsEnvironment,
+{
+    Renderer::new(MiniJinjaEngine::new(env, name))
+}
+
+/// Create a `Renderer` which selects the template by the name provided from the context value.
+pub fn minijinja_dynamic<E>(env: E) -> Renderer<DynamicMiniJinjaEngine<E>>
+where
+    E: AsEnvironment,
+{
+    Renderer::new(DynamicMiniJinjaEngine::new(env))
+}
+
+#[derive(Debug)]
+pub struct MiniJinjaEngine<E> {
+    env: E,
+    name: Cow<'static, str>,
+    content_type: Option<HeaderValue>,
+}
+
+impl<E> MiniJinjaEngine<E>
+where
+    E: AsEnvironment,
+{
+    pub fn new(env: E, name: impl Into<Cow<'static, str>>) -> MiniJinjaEngine<E> {
+        let name = name.into();
+        let content_type = guess_content_type(&name);
+        MiniJinjaEngine {
+            env,
+            name,
+            content_type,
+        }
+    }
+}
+
+impl<E, CtxT: Serialize> Engine<CtxT> for MiniJinjaEngine<E>
+where
+    E: AsEnvironment,
+{
+    type Body = String;
+    type Error = failure::Error;
+
+    fn content_type_hint(&self, _: &CtxT) -> Option<HeaderValue> {
+        self.content_type.clone()
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        render_minijinja(self.env.as_environment(), &self.name, &value)
+    }
+}
+
+impl<E> Validate for MiniJinjaEngine<E>
+where
+    E: AsEnvironment,
+{
+    fn validate(&self) -> Result<(), failure::Error> {
+        self.env
+            .as_environment()
+            .get_template(&self.name)
+            .map(|_| ())
+            .map_err(|err| match err.kind() {
+                ErrorKind::TemplateNotFound => TemplateNotFound::new(&*self.name).into(),
+                _ => minijinja_error(err),
+            })
+    }
+}
+
+impl<E, CtxT> HasTemplate<CtxT> for MiniJinjaEngine<E>
+where
+    E: AsEnvironment,
+{
+    fn has_template(&self, _: &CtxT) -> bool {
+        self.env.as_environment().get_template(&self.name).is_ok()
+    }
+}
+
+/// A template engine which renders the template chosen by the context value.
+#[derive(Debug)]
+pub struct DynamicMiniJinjaEngine<E> {
+    env: E,
+}
+
+impl<E> DynamicMiniJinjaEngine<E>
+where
+    E: AsEnvironment,
+{
+    pub fn new(env: E) -> DynamicMiniJinjaEngine<E> {
+        DynamicMiniJinjaEngine { env }
+    }
+}
+
+impl<E, CtxT> Engine<CtxT> for DynamicMiniJinjaEngine<E>
+where
+    E: AsEnvironment,
+    CtxT: Serialize + TemplateName,
+{
+    type Body = String;
+    type Error = failure::Error;
+
+    fn content_type_hint(&self, value: &CtxT) -> Option<HeaderValue> {
+        guess_content_type(value.template_name())
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        render_minijinja(self.env.as_environment(), value.template_name(), &value)
+    }
+}
+
+impl<E, CtxT> HasTemplate<CtxT> for DynamicMiniJinjaEngine<E>
+where
+    E: AsEnvironment,
+    CtxT: TemplateName,
+{
+    fn has_template(&self, value: &CtxT) -> bool {
+        self.env
+            .as_environment()
+            .get_template(value.template_name())
+            .is_ok()
+    }
+}
+
+fn render_minijinja<CtxT: Serialize>(
+    env: &Environment<'static>,
+    name: &str,
+    value: &CtxT,
+) -> Result<String, failure::Error> {
+    env.get_template(name)
+        .and_then(|template| template.render(value))
+        .map_err(minijinja_error)
+}
+
+/// Converts the error from MiniJinja into one whose message contains the whole error chain.
+///
+/// Each error in the chain reports the name of template and the line number where it occurred,
+/// so the errors raised in the included templates are located as well.
+fn minijinja_error(err: minijinja::Error) -> failure::Error {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    failure::Error::from(err).context(message).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DynamicMiniJinjaEngine, MiniJinjaEngine};
+    use backend::engine::{Engine, TemplateNotFound, Validate};
+    use backend::template_name::Named;
+    use minijinja::Environment;
+    use serde::Serialize;
+    use std::sync::Arc;
+
+    #[derive(Debug, Serialize)]
+    struct Context {
+        name: String,
+    }
+
+    #[test]
+    fn test_minijinja() {
+        let mut env = Environment::new();
+        env.add_template("index.html", "Hello, {{ name | upper }}.")
+            .unwrap();
+
+        let engine = MiniJinjaEngine::new(Arc::new(env), "index.html");
+        let value = Context {
+            name: "Alice".into(),
+        };
+        assert_matches!(
+            engine.content_type_hint(&value),
+            Some(ref h) if h == "text/html"
+        );
+        assert_eq!(engine.render(value).unwrap(), "Hello, ALICE.");
+    }
+
+    #[test]
+    fn test_minijinja_dynamic() {
+        let mut env = Environment::new();
+        env.add_template("mail.txt", "Dear {{ name }}").unwrap();
+
+        let engine = DynamicMiniJinjaEngine::new(env);
+        let value = Named::new(
+            "mail.txt",
+            Context {
+                name: "Alice".into(),
+            },
+        );
+        assert_matches!(
+            engine.content_type_hint(&value),
+            Some(ref h) if h == "text/plain"
+        );
+        assert_eq!(engine.render(value).unwrap(), "Dear Alice");
+    }
+
+    #[test]
+    fn test_minijinja_error() {
+        let mut env = Environment::new();
+        env.add_template("index.html", "line 1\n{% include 'missing.html' %}")
+            .unwrap();
+        let env = Arc::new(env);
+
+        let engine = MiniJinjaEngine::new(env.clone(), "index.html");
+        let value = Context {
+            name: "Alice".into(),
+        };
+        let err = engine.render(value).unwrap_err();
+        assert!(
+            err.to_string().contains("(in index.html:2)"),
+            "unexpected message: {}",
+            err
+        );
+
+        assert!(engine.validate().is_ok());
+        let err = MiniJinjaEngine::new(env, "missing.html")
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TemplateNotFound>().unwrap().name(),
+            "missing.html"
+        );
+    }
+}
@@ -1,21 +1,100 @@
 #![allow(missing_docs)]
 
 pub(crate) mod askama;
+pub(crate) mod boxed;
+pub(crate) mod cache;
+pub(crate) mod cbor;
+pub(crate) mod combinator;
+pub(crate) mod embed;
 pub(crate) mod engine;
 pub(crate) mod handlebars;
 pub(crate) mod horrorshow;
+pub(crate) mod json;
+pub(crate) mod liquid;
+pub(crate) mod maud;
+pub(crate) mod minijinja;
+pub(crate) mod msgpack;
+pub(crate) mod mustache;
+pub(crate) mod reload;
+pub(crate) mod ructe;
+pub(crate) mod sailfish;
+pub(crate) mod serializer;
+pub(crate) mod template_dir;
+pub(crate) mod template_name;
 pub(crate) mod tera;
+pub(crate) mod yaml;
 
-pub use self::engine::Engine;
+pub use self::boxed::{BoxBody, BoxEngine};
+pub use self::cache::{CacheHandle, CachedEngine, Keyed};
+pub use self::combinator::{EitherEngine, FallbackEngine};
+pub use self::engine::{Engine, HasTemplate, StreamingEngine, TemplateNotFound, Validate};
 
 #[cfg(feature = "use-askama")]
 pub use self::askama::AskamaEngine;
 
+#[cfg(feature = "use-cbor")]
+pub use self::cbor::{Cbor, CborEngine};
+
 #[cfg(feature = "use-handlebars")]
-pub use self::handlebars::{AsHandlebars, HandlebarsEngine};
+pub use self::handlebars::{
+    AsHandlebars, DynamicHandlebarsEngine, HandlebarsEngine, WithHandlebars,
+};
 
 #[cfg(feature = "use-horrorshow")]
 pub use self::horrorshow::HorrorshowEngine;
 
+#[cfg(feature = "use-json")]
+pub use self::json::{Json, JsonEngine};
+
+#[cfg(feature = "use-maud")]
+pub use self::maud::MaudEngine;
+
+#[cfg(feature = "use-minijinja")]
+pub use self::minijinja::{AsEnvironment, DynamicMiniJinjaEngine, MiniJinjaEngine};
+
+#[cfg(feature = "use-msgpack")]
+pub use self::msgpack::{MsgPack, MsgPackEngine};
+
+#[cfg(feature = "use-mustache")]
+pub use self::mustache::{
+    AsMustache, DynamicMustacheEngine, MustacheEngine, MustacheTemplates, WithMustache,
+};
+
+#[cfg(any(feature = "use-tera", feature = "use-handlebars"))]
+pub use self::embed::EmbeddedTemplates;
+
+#[cfg(any(
+    feature = "use-tera",
+    feature = "use-handlebars",
+    feature = "use-mustache"
+))]
+pub use self::reload::Reloadable;
+
+#[cfg(any(feature = "use-tera", feature = "use-handlebars"))]
+pub use self::template_dir::{TemplateDir, TemplateDirError, TemplateError};
+
+#[cfg(feature = "serde-context")]
+pub use self::template_name::{Named, TemplateName};
+
+#[cfg(feature = "use-liquid")]
+pub use self::liquid::{AsLiquid, DynamicLiquidEngine, LiquidEngine, LiquidTemplates};
+
+#[cfg(feature = "use-ructe")]
+pub use self::ructe::RucteEngine;
+
+#[cfg(feature = "use-sailfish")]
+pub use self::sailfish::SailfishEngine;
+
+#[cfg(any(
+    feature = "use-json",
+    feature = "use-yaml",
+    feature = "use-cbor",
+    feature = "use-msgpack"
+))]
+pub use self::serializer::{Format, SerializerEngine};
+
 #[cfg(feature = "use-tera")]
-pub use self::tera::{AsTera, TeraEngine};
+pub use self::tera::{AsTera, DynamicTeraEngine, TeraEngine, WithTera};
+
+#[cfg(feature = "use-yaml")]
+pub use self::yaml::{Yaml, YamlEngine};
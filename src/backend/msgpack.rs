@@ -0,0 +1,73 @@
+#![cfg(feature = "use-msgpack")]
+
+use http::header::HeaderValue;
+use serde::Serialize;
+
+use super::serializer::{Format, SerializerEngine};
+use renderer::Renderer;
+
+/// Create a `Renderer` which serializes the context values into MessagePack.
+pub fn msgpack() -> Renderer<MsgPackEngine> {
+    Renderer::new(MsgPackEngine::default())
+}
+
+/// A template engine which serializes the context values into MessagePack.
+pub type MsgPackEngine = SerializerEngine<MsgPack>;
+
+/// The MessagePack format.
+///
+/// The structs are serialized as maps keyed by the field names, so that
+/// the output can be decoded without knowing the order of fields.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MsgPack {
+    _priv: (),
+}
+
+impl Format for MsgPack {
+    type Body = Vec<u8>;
+    type Error = ::rmp_serde::encode::Error;
+
+    fn content_type(&self) -> HeaderValue {
+        HeaderValue::from_static("application/msgpack")
+    }
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Self::Body, Self::Error> {
+        ::rmp_serde::to_vec_named(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MsgPack, MsgPackEngine};
+    use backend::engine::Engine;
+    use backend::serializer::tests::assert_serialize_error;
+
+    use serde::Serialize;
+
+    #[test]
+    fn test_msgpack() {
+        #[derive(Debug, Serialize)]
+        struct Context {
+            name: String,
+        }
+
+        let value = Context {
+            name: "Alice".into(),
+        };
+
+        let engine = MsgPackEngine::default();
+        assert_matches!(
+            engine.content_type_hint(&value),
+            Some(ref h) if h == "application/msgpack"
+        );
+        assert_eq!(
+            engine.render(value).unwrap(),
+            b"\x81\xa4name\xa5Alice".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_msgpack_error() {
+        assert_serialize_error(MsgPack::default());
+    }
+}
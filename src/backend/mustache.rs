@@ -0,0 +1,355 @@
+#![cfg(feature = "use-mustache")]
+
+use failure;
+use http::header::HeaderValue;
+use mustache::{Context, Template};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::engine::{Engine, HasTemplate, TemplateNotFound, Validate};
+use super::reload::{walk_dir, Reloadable};
+use super::template_name::{guess_content_type, TemplateName};
+use renderer::Renderer;
+
+const EXTENSION: &str = ".mustache";
+
+pub trait AsMustache {
+    fn as_mustache(&self) -> &MustacheTemplates;
+}
+
+impl AsMustache for MustacheTemplates {
+    fn as_mustache(&self) -> &MustacheTemplates {
+        self
+    }
+}
+
+impl<T: AsMustache> AsMustache for Box<T> {
+    fn as_mustache(&self) -> &MustacheTemplates {
+        (**self).as_mustache()
+    }
+}
+
+impl<T: AsMustache> AsMustache for ::std::rc::Rc<T> {
+    fn as_mustache(&self) -> &MustacheTemplates {
+        (**self).as_mustache()
+    }
+}
+
+impl<T: AsMustache> AsMustache for ::std::sync::Arc<T> {
+    fn as_mustache(&self) -> &MustacheTemplates {
+        (**self).as_mustache()
+    }
+}
+
+/// A trait representing a source of `MustacheTemplates` which is accessed only within a closure.
+///
+/// This trait is implemented for all types implementing `AsMustache`, and for
+/// `Reloadable<MustacheTemplates>` whose registry may be replaced between two renderings.
+pub trait WithMustache {
+    fn with_mustache<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&MustacheTemplates) -> R;
+}
+
+impl<T: AsMustache> WithMustache for T {
+    fn with_mustache<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&MustacheTemplates) -> R,
+    {
+        f(self.as_mustache())
+    }
+}
+
+impl WithMustache for Reloadable<MustacheTemplates> {
+    fn with_mustache<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&MustacheTemplates) -> R,
+    {
+        f(&self.registry())
+    }
+}
+
+impl WithMustache for ::std::sync::Arc<Reloadable<MustacheTemplates>> {
+    fn with_mustache<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&MustacheTemplates) -> R,
+    {
+        (**self).with_mustache(f)
+    }
+}
+
+impl Reloadable<MustacheTemplates> {
+    /// Create a `Reloadable<MustacheTemplates>` which loads all `.mustache` files under `dir`.
+    pub fn mustache(
+        dir: impl Into<PathBuf>,
+    ) -> Result<Reloadable<MustacheTemplates>, failure::Error> {
+        Reloadable::new(dir, MustacheTemplates::load_dir)
+    }
+}
+
+/// A set of compiled Mustache templates keyed by their names.
+#[derive(Debug, Default)]
+pub struct MustacheTemplates {
+    templates: HashMap<String, Template>,
+}
+
+impl MustacheTemplates {
+    /// Create an empty `MustacheTemplates`.
+    pub fn new() -> MustacheTemplates {
+        MustacheTemplates::default()
+    }
+
+    /// Loads all `.mustache` files under `dir`.
+    ///
+    /// Each template is registered by its `/`-separated path relative to the directory
+    /// without the `.mustache` extension (e.g. `pages/index.html.mustache` is registered
+    /// as `pages/index.html`), so that the content type can be guessed from the name.
+    /// The partials (`{{> name}}`) are resolved relative to the directory as well.
+    pub fn load_dir(dir: &Path) -> Result<MustacheTemplates, failure::Error> {
+        let context = Context::new(dir.to_owned());
+        let mut templates = MustacheTemplates::new();
+        for (path, name) in walk_dir(dir)? {
+            if !name.ends_with(EXTENSION) {
+                continue;
+            }
+            let name = &name[..name.len() - EXTENSION.len()];
+            let content = fs::read_to_string(&path)?;
+            let template = context
+                .compile(content.chars())
+                .map_err(|err| failure::format_err!("{}: {}", name, err))?;
+            templates.insert(name, template);
+        }
+        Ok(templates)
+    }
+
+    /// Registers a compiled template with the specified name.
+    pub fn insert(&mut self, name: impl Into<String>, template: Template) {
+        self.templates.insert(name.into(), template);
+    }
+
+    /// Returns the template registered with the specified name.
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+}
+
+pub fn mustache<M>(templates: M, name: impl Into<Cow<'static, str>>) -> Renderer<MustacheEngine<M>>
+where
+    M: WithMustache,
+{
+    Renderer::new(MustacheEngine::new(templates, name))
+}
+
+/// Create a `Renderer` which selects the template by the name provided from the context value.
+pub fn mustache_dynamic<M>(templates: M) -> Renderer<DynamicMustacheEngine<M>>
+where
+    M: WithMustache,
+{
+    Renderer::new(DynamicMustacheEngine::new(templates))
+}
+
+#[derive(Debug)]
+pub struct MustacheEngine<M> {
+    templates: M,
+    name: Cow<'static, str>,
+    content_type: Option<HeaderValue>,
+}
+
+impl<M> MustacheEngine<M>
+where
+    M: WithMustache,
+{
+    pub fn new(templates: M, name: impl Into<Cow<'static, str>>) -> MustacheEngine<M> {
+        let name = name.into();
+        let content_type = guess_content_type(&name);
+        MustacheEngine {
+            templates,
+            name,
+            content_type,
+        }
+    }
+}
+
+impl<M, CtxT: Serialize> Engine<CtxT> for MustacheEngine<M>
+where
+    M: WithMustache,
+{
+    type Body = String;
+    type Error = failure::Error;
+
+    fn content_type_hint(&self, _: &CtxT) -> Option<HeaderValue> {
+        self.content_type.clone()
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        self.templates
+            .with_mustache(|templates| render_mustache(templates, &self.name, &value))
+    }
+}
+
+impl<M> Validate for MustacheEngine<M>
+where
+    M: WithMustache,
+{
+    fn validate(&self) -> Result<(), failure::Error> {
+        self.templates
+            .with_mustache(|templates| get_template(templates, &self.name).map(|_| ()))
+    }
+}
+
+impl<M, CtxT> HasTemplate<CtxT> for MustacheEngine<M>
+where
+    M: WithMustache,
+{
+    fn has_template(&self, _: &CtxT) -> bool {
+        self.templates
+            .with_mustache(|templates| templates.get(&self.name).is_some())
+    }
+}
+
+/// A template engine which renders the template chosen by the context value.
+#[derive(Debug)]
+pub struct DynamicMustacheEngine<M> {
+    templates: M,
+}
+
+impl<M> DynamicMustacheEngine<M>
+where
+    M: WithMustache,
+{
+    pub fn new(templates: M) -> DynamicMustacheEngine<M> {
+        DynamicMustacheEngine { templates }
+    }
+}
+
+impl<M, CtxT> Engine<CtxT> for DynamicMustacheEngine<M>
+where
+    M: WithMustache,
+    CtxT: Serialize + TemplateName,
+{
+    type Body = String;
+    type Error = failure::Error;
+
+    fn content_type_hint(&self, value: &CtxT) -> Option<HeaderValue> {
+        guess_content_type(value.template_name())
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        self.templates
+            .with_mustache(|templates| render_mustache(templates, value.template_name(), &value))
+    }
+}
+
+impl<M, CtxT> HasTemplate<CtxT> for DynamicMustacheEngine<M>
+where
+    M: WithMustache,
+    CtxT: TemplateName,
+{
+    fn has_template(&self, value: &CtxT) -> bool {
+        self.templates
+            .with_mustache(|templates| templates.get(value.template_name()).is_some())
+    }
+}
+
+fn get_template<'a>(
+    templates: &'a MustacheTemplates,
+    name: &str,
+) -> Result<&'a Template, failure::Error> {
+    templates
+        .get(name)
+        .ok_or_else(|| TemplateNotFound::new(name).into())
+}
+
+fn render_mustache<CtxT: Serialize>(
+    templates: &MustacheTemplates,
+    name: &str,
+    value: &CtxT,
+) -> Result<String, failure::Error> {
+    let template = get_template(templates, name)?;
+    Ok(template.render_to_string(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DynamicMustacheEngine, MustacheEngine, MustacheTemplates};
+    use backend::engine::{Engine, TemplateNotFound, Validate};
+    use backend::reload::tests::temp_dir;
+    use backend::reload::Reloadable;
+    use backend::template_name::Named;
+    use serde::Serialize;
+    use std::fs;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[derive(Debug, Serialize)]
+    struct Context {
+        name: String,
+    }
+
+    #[test]
+    fn test_mustache_load_dir() {
+        let dir = temp_dir("mustache-load-dir");
+        fs::create_dir_all(dir.join("mail")).unwrap();
+        fs::write(dir.join("header.mustache"), "<h1>{{ name }}</h1>").unwrap();
+        fs::write(dir.join("index.html.mustache"), "{{> header}}<p>Hi</p>").unwrap();
+        fs::write(dir.join("mail/welcome.txt.mustache"), "Dear {{ name }}").unwrap();
+        fs::write(dir.join("notes.txt"), "notes").unwrap();
+
+        let templates = Arc::new(MustacheTemplates::load_dir(&dir).unwrap());
+        assert!(templates.get("notes.txt").is_none());
+
+        let engine = MustacheEngine::new(templates.clone(), "index.html");
+        let value = Context {
+            name: "Alice".into(),
+        };
+        assert_matches!(
+            engine.content_type_hint(&value),
+            Some(ref h) if h == "text/html"
+        );
+        assert_eq!(engine.render(value).unwrap(), "<h1>Alice</h1><p>Hi</p>");
+        assert!(engine.validate().is_ok());
+        let err = MustacheEngine::new(templates.clone(), "missing.html")
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TemplateNotFound>().unwrap().name(),
+            "missing.html"
+        );
+
+        let engine = DynamicMustacheEngine::new(templates);
+        let value = Named::new(
+            "mail/welcome.txt",
+            Context {
+                name: "Alice".into(),
+            },
+        );
+        assert_matches!(
+            engine.content_type_hint(&value),
+            Some(ref h) if h == "text/plain"
+        );
+        assert_eq!(engine.render(value).unwrap(), "Dear Alice");
+    }
+
+    #[test]
+    fn test_mustache_reloadable() {
+        let dir = temp_dir("mustache-reloadable");
+        fs::write(dir.join("index.html.mustache"), "Hello, {{ name }}.").unwrap();
+
+        let templates = Reloadable::mustache(&dir)
+            .unwrap()
+            .poll_interval(Duration::from_millis(0));
+        let engine = MustacheEngine::new(templates, "index.html");
+        let value = Context {
+            name: "Alice".into(),
+        };
+        assert_eq!(engine.render(value).unwrap(), "Hello, Alice.");
+
+        fs::write(dir.join("index.html.mustache"), "Bye, {{ name }}.").unwrap();
+        engine.templates.reload().unwrap();
+        let value = Context { name: "Bob".into() };
+        assert_eq!(engine.render(value).unwrap(), "Bye, Bob.");
+    }
+}
@@ -0,0 +1,303 @@
+#![cfg(any(
+    feature = "use-tera",
+    feature = "use-handlebars",
+    feature = "use-mustache"
+))]
+
+use failure;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, TryLockError};
+use std::time::{Duration, Instant, SystemTime};
+
+type Loader<R> = dyn Fn(&Path) -> Result<R, failure::Error> + Send + Sync + 'static;
+
+/// A template registry which is reloaded from a directory when the files in it are modified.
+///
+/// The directory is polled before the registry is accessed, at most once per
+/// `poll_interval`. When a reload fails, the last successfully loaded registry
+/// is kept, the error can be retrieved by `take_error`, and the reload is
+/// retried at the next poll.
+///
+/// The new registry is loaded without blocking the renderings in progress,
+/// which keep using the previous one until they complete.
+///
+/// This type is intended to be used in development, and `WithTera`/`WithHandlebars`/`WithMustache`
+/// are implemented for `Reloadable<Tera>`/`Reloadable<Handlebars>`/`Reloadable<MustacheTemplates>`.
+pub struct Reloadable<R> {
+    dir: PathBuf,
+    poll_interval: Duration,
+    loader: Box<Loader<R>>,
+    registry: RwLock<Arc<R>>,
+    state: Mutex<State>,
+    loading: Mutex<()>,
+}
+
+#[derive(Debug)]
+struct State {
+    last_checked: Instant,
+    snapshot: Snapshot,
+    error: Option<failure::Error>,
+}
+
+impl<R> fmt::Debug for Reloadable<R>
+where
+    R: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reloadable")
+            .field("dir", &self.dir)
+            .field("poll_interval", &self.poll_interval)
+            .field("registry", &self.registry)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<R> Reloadable<R> {
+    /// Create a new `Reloadable` which loads the registry from `dir` by using the specified function.
+    ///
+    /// The registry is loaded once at construction, and the error is returned
+    /// if the initial load fails.
+    pub fn new<F>(dir: impl Into<PathBuf>, loader: F) -> Result<Reloadable<R>, failure::Error>
+    where
+        F: Fn(&Path) -> Result<R, failure::Error> + Send + Sync + 'static,
+    {
+        let dir = dir.into();
+        let snapshot = Snapshot::take(&dir)?;
+        let registry = loader(&dir)?;
+        Ok(Reloadable {
+            dir,
+            poll_interval: Duration::from_millis(500),
+            loader: Box::new(loader),
+            registry: RwLock::new(Arc::new(registry)),
+            state: Mutex::new(State {
+                last_checked: Instant::now(),
+                snapshot,
+                error: None,
+            }),
+            loading: Mutex::new(()),
+        })
+    }
+
+    /// Sets the minimum interval between two checks of the template directory.
+    pub fn poll_interval(mut self, interval: Duration) -> Reloadable<R> {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Returns the path of the watched template directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Reloads the registry immediately, regardless of whether the files are modified.
+    ///
+    /// When the reload fails, the last successfully loaded registry is kept.
+    pub fn reload(&self) -> Result<(), failure::Error> {
+        let _loading = self.loading.lock().unwrap_or_else(PoisonError::into_inner);
+        let snapshot = Snapshot::take(&self.dir)?;
+        self.state().last_checked = Instant::now();
+        self.load(snapshot)
+    }
+
+    /// Takes the error occurred at the last automatic reload, if any.
+    pub fn take_error(&self) -> Option<failure::Error> {
+        self.state().error.take()
+    }
+
+    /// Returns the current registry, reloading it before if the files are modified.
+    pub fn registry(&self) -> Arc<R> {
+        self.poll();
+        self.registry
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn poll(&self) {
+        {
+            let mut state = self.state();
+            if state.last_checked.elapsed() < self.poll_interval {
+                return;
+            }
+            state.last_checked = Instant::now();
+        }
+
+        // Another thread is reloading the registry, so use the current one until it completes.
+        let _loading = match self.loading.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+
+        let result = Snapshot::take(&self.dir)
+            .map_err(Into::into)
+            .and_then(|snapshot| {
+                if snapshot == self.state().snapshot {
+                    return Ok(());
+                }
+                self.load(snapshot)
+            });
+        if let Err(err) = result {
+            self.state().error = Some(err);
+        }
+    }
+
+    /// Loads the registry without holding the locks and then swaps it with the current one.
+    ///
+    /// The snapshot is recorded only after a successful load, so that a failed
+    /// reload is retried even if the files are not modified again.
+    fn load(&self, snapshot: Snapshot) -> Result<(), failure::Error> {
+        let registry = Arc::new((self.loader)(&self.dir)?);
+        *self
+            .registry
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = registry;
+        self.state().snapshot = snapshot;
+        Ok(())
+    }
+}
+
+/// The modification times and sizes of all files in a template directory.
+///
+/// The sizes are compared as well, since the edits within the granularity of
+/// modification times are not distinguished by them.
+#[derive(Debug, PartialEq)]
+struct Snapshot(Vec<(PathBuf, Option<SystemTime>, u64)>);
+
+impl Snapshot {
+    fn take(dir: &Path) -> io::Result<Snapshot> {
+        let mut entries = vec![];
+        for (path, _) in walk_dir(dir)? {
+            let metadata = fs::metadata(&path)?;
+            let modified = metadata.modified().ok();
+            entries.push((path, modified, metadata.len()));
+        }
+        Ok(Snapshot(entries))
+    }
+}
+
+/// Collects the paths of all files under `dir`, paired with their `/`-separated relative names.
+///
+/// Hidden files and directories are skipped, and the result is sorted by name.
+pub(crate) fn walk_dir(dir: &Path) -> io::Result<Vec<(PathBuf, String)>> {
+    fn walk(dir: &Path, prefix: &str, files: &mut Vec<(PathBuf, String)>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let file_name = match file_name.to_str() {
+                Some(name) if !name.starts_with('.') => name,
+                _ => continue,
+            };
+            let name = format!("{}{}", prefix, file_name);
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                walk(&path, &format!("{}/", name), files)?;
+            } else {
+                files.push((path, name));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = vec![];
+    walk(dir, "", &mut files)?;
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(files)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::Reloadable;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Creates an empty temporary directory unique to the calling test.
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = env::temp_dir().join(format!(
+            "finchers-template-{}-{}-{}",
+            name,
+            ::std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_walk_dir() {
+        let dir = temp_dir("walk-dir");
+        fs::create_dir_all(dir.join("pages/.hidden")).unwrap();
+        fs::write(dir.join("index.html"), "").unwrap();
+        fs::write(dir.join("pages/about.html"), "").unwrap();
+        fs::write(dir.join("pages/.hidden/secret.html"), "").unwrap();
+        fs::write(dir.join(".swp"), "").unwrap();
+
+        let names: Vec<_> = super::walk_dir(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect();
+        assert_eq!(names, vec!["index.html", "pages/about.html"]);
+    }
+
+    #[test]
+    fn test_reload_while_registry_in_use() {
+        let dir = temp_dir("reload-in-use");
+        fs::write(dir.join("index.html"), "v1").unwrap();
+
+        let registry = Reloadable::new(&dir, |dir| Ok(fs::read_to_string(dir.join("index.html"))?))
+            .unwrap()
+            .poll_interval(Duration::from_millis(0));
+
+        // the registry held by a rendering in progress does not block the reload.
+        let in_use = registry.registry();
+        fs::write(dir.join("index.html"), "v2").unwrap();
+        registry.reload().unwrap();
+        assert_eq!(*in_use, "v1");
+        assert_eq!(*registry.registry(), "v2");
+    }
+
+    #[test]
+    fn test_reload_retried_after_failure() {
+        let dir = temp_dir("reload-retry");
+        fs::write(dir.join("index.html"), "v1").unwrap();
+
+        let fail = Arc::new(AtomicBool::new(false));
+        let registry = {
+            let fail = fail.clone();
+            Reloadable::new(&dir, move |dir| {
+                if fail.load(Ordering::SeqCst) {
+                    return Err(failure::err_msg("failed to load"));
+                }
+                Ok(fs::read_to_string(dir.join("index.html"))?)
+            })
+            .unwrap()
+            .poll_interval(Duration::from_millis(0))
+        };
+
+        // the size differs, so the modification is detected even within the same mtime.
+        fs::write(dir.join("index.html"), "v2 (edited)").unwrap();
+        fail.store(true, Ordering::SeqCst);
+        assert_eq!(*registry.registry(), "v1");
+        assert!(registry.take_error().is_some());
+
+        // the files are not modified again, but the failed reload is retried.
+        fail.store(false, Ordering::SeqCst);
+        assert_eq!(*registry.registry(), "v2 (edited)");
+        assert!(registry.take_error().is_none());
+    }
+}
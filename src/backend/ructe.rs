@@ -0,0 +1,130 @@
+#![cfg(feature = "use-ructe")]
+
+use http::header::HeaderValue;
+use mime::Mime;
+use std::fmt;
+use std::io;
+
+use super::engine::Engine;
+use renderer::Renderer;
+
+/// Create a `Renderer` from a function which writes the template into a buffer.
+///
+/// The functions generated by ructe take the output and the template arguments,
+/// so they are adapted by a closure as follows:
+///
+/// ```ignore
+/// let renderer = finchers_template::ructe(|out: &mut Vec<u8>, ctx: Context| {
+///     templates::index_html(out, &ctx.title, &ctx.items)
+/// });
+/// ```
+pub fn ructe<F>(template: F) -> Renderer<RucteEngine<F>> {
+    Renderer::new(RucteEngine::new(template))
+}
+
+/// A template engine which renders the values by a function of the form
+/// `Fn(&mut Vec<u8>, CtxT) -> io::Result<()>`, such as the ones generated by ructe.
+///
+/// The trait objects (e.g. `Box<dyn Fn(&mut Vec<u8>, CtxT) -> io::Result<()> + Send + Sync>`)
+/// can also be used, since they implement `Fn`.
+pub struct RucteEngine<F> {
+    template: F,
+    content_type: Option<HeaderValue>,
+}
+
+impl<F> fmt::Debug for RucteEngine<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RucteEngine")
+            .field("content_type", &self.content_type)
+            .finish()
+    }
+}
+
+impl<F> RucteEngine<F> {
+    pub fn new(template: F) -> RucteEngine<F> {
+        RucteEngine {
+            template,
+            content_type: None,
+        }
+    }
+
+    /// Sets the content-type of the rendered bodies.
+    ///
+    /// If not set, the default value of `Renderer` (`text/html`) is used.
+    pub fn content_type(mut self, value: &Mime) -> RucteEngine<F> {
+        self.content_type = Some(
+            value
+                .as_ref()
+                .parse()
+                .expect("should be a valid header value"),
+        );
+        self
+    }
+}
+
+impl<F, CtxT> Engine<CtxT> for RucteEngine<F>
+where
+    F: Fn(&mut Vec<u8>, CtxT) -> io::Result<()>,
+{
+    type Body = Vec<u8>;
+    type Error = io::Error;
+
+    fn content_type_hint(&self, _: &CtxT) -> Option<HeaderValue> {
+        self.content_type.clone()
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        let mut body = vec![];
+        (self.template)(&mut body, value)?;
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RucteEngine;
+    use backend::engine::Engine;
+    use renderer::Renderer;
+
+    use finchers::prelude::*;
+    use finchers::test;
+    use mime;
+    use std::io;
+    use std::io::Write;
+
+    // An equivalent of the function generated by ructe.
+    fn hello_html<W: Write>(out: &mut W, name: &str) -> io::Result<()> {
+        write!(out, "<p>Hello, {}</p>", name)
+    }
+
+    #[test]
+    #[allow(clippy::io_other_error)] // `io::Error::other` requires Rust 1.74
+    fn test_ructe() {
+        let engine = RucteEngine::new(|out: &mut Vec<u8>, name: &str| hello_html(out, name));
+        assert_eq!(engine.content_type_hint(&"Alice"), None);
+        assert_eq!(engine.render("Alice").unwrap(), b"<p>Hello, Alice</p>");
+
+        let engine = RucteEngine::new(|_: &mut Vec<u8>, _: ()| {
+            Err(io::Error::new(io::ErrorKind::Other, "failed"))
+        });
+        assert!(engine.render(()).is_err());
+    }
+
+    #[test]
+    fn test_ructe_trait_object() {
+        type Template = Box<dyn Fn(&mut Vec<u8>, String) -> io::Result<()> + Send + Sync>;
+        let template: Template = Box::new(|out, name| hello_html(out, &name));
+
+        let mut runner = test::runner({
+            endpoint::cloned("Alice".to_owned()).wrap(Renderer::new(
+                RucteEngine::new(template).content_type(&mime::TEXT_PLAIN_UTF_8),
+            ))
+        });
+        let response = runner.perform("/").unwrap();
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(response.body().to_utf8().unwrap(), "<p>Hello, Alice</p>");
+    }
+}
@@ -0,0 +1,124 @@
+#![cfg(feature = "use-sailfish")]
+
+use super::engine::Engine;
+use renderer::Renderer;
+
+use http::header::HeaderValue;
+use mime_guess::from_ext;
+use sailfish::{RenderError, TemplateOnce};
+use std::marker::PhantomData;
+
+/// Create a `Renderer` for the templates generated by sailfish.
+///
+/// The generated templates do not expose their paths, so the content-type
+/// cannot be guessed and the default value of `Renderer` (`text/html`) is
+/// always used. Use `sailfish_with_extension` for the other kinds of templates.
+pub fn sailfish<CtxT: TemplateOnce>() -> Renderer<SailfishEngine<CtxT>> {
+    Renderer::new(SailfishEngine::default())
+}
+
+/// Create a `Renderer` for the templates generated by sailfish, whose
+/// content-type is guessed from the extension of the template file
+/// (e.g. `"txt"` for `#[template(path = "mail.txt")]`).
+pub fn sailfish_with_extension<CtxT: TemplateOnce>(ext: &str) -> Renderer<SailfishEngine<CtxT>> {
+    Renderer::new(SailfishEngine::default().extension(ext))
+}
+
+#[derive(Debug)]
+pub struct SailfishEngine<CtxT> {
+    content_type: Option<HeaderValue>,
+    _marker: PhantomData<fn(CtxT)>,
+}
+
+impl<CtxT> Default for SailfishEngine<CtxT> {
+    fn default() -> Self {
+        SailfishEngine {
+            content_type: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<CtxT: TemplateOnce> SailfishEngine<CtxT> {
+    /// Sets the extension of the template file (e.g. `"html"` for
+    /// `#[template(path = "index.html")]`), from which the value of
+    /// content-type is guessed.
+    ///
+    /// The generated templates do not expose their paths, so the default
+    /// value of `Renderer` (`text/html`) is used unless the extension is set.
+    pub fn extension(mut self, ext: &str) -> SailfishEngine<CtxT> {
+        self.content_type = from_ext(ext)
+            .first_raw()
+            .map(|mime_str| mime_str.parse().expect("should be a valid header value"));
+        self
+    }
+}
+
+impl<CtxT: TemplateOnce> Engine<CtxT> for SailfishEngine<CtxT> {
+    type Body = String;
+    type Error = RenderError;
+
+    fn content_type_hint(&self, _: &CtxT) -> Option<HeaderValue> {
+        self.content_type.clone()
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        value.render_once()
+    }
+}
+
+#[test]
+fn test_sailfish() {
+    use sailfish::runtime::Buffer;
+
+    #[derive(Debug)]
+    struct Context {
+        name: String,
+    }
+
+    impl TemplateOnce for Context {
+        fn render_once(self) -> Result<String, RenderError> {
+            let mut buf = Buffer::new();
+            self.render_once_to(&mut buf)?;
+            Ok(buf.into_string())
+        }
+
+        fn render_once_to(self, buf: &mut Buffer) -> Result<(), RenderError> {
+            if self.name.is_empty() {
+                return Err(RenderError::Msg("empty name".into()));
+            }
+            buf.push_str(&self.name);
+            Ok(())
+        }
+    }
+
+    let engine = SailfishEngine::default();
+    let value = Context {
+        name: "Alice".into(),
+    };
+    assert_matches!(engine.content_type_hint(&value), None);
+    assert_matches!(
+        engine.render(value),
+        Ok(ref body) if body == "Alice"
+    );
+
+    let engine = engine.extension("txt");
+    let value = Context { name: "Bob".into() };
+    assert_matches!(
+        engine.content_type_hint(&value),
+        Some(ref h) if h == "text/plain"
+    );
+    assert_matches!(
+        engine.render(value),
+        Ok(ref body) if body == "Bob"
+    );
+    assert_matches!(
+        sailfish_with_extension::<Context>("txt").engine().content_type_hint(&Context { name: "Carol".into() }),
+        Some(ref h) if h == "text/plain"
+    );
+
+    let value = Context {
+        name: String::new(),
+    };
+    assert_matches!(engine.render(value), Err(RenderError::Msg(..)));
+}
@@ -0,0 +1,97 @@
+#![cfg(any(
+    feature = "use-json",
+    feature = "use-yaml",
+    feature = "use-cbor",
+    feature = "use-msgpack"
+))]
+
+use failure;
+use finchers::output::body::ResBody;
+use http::header::HeaderValue;
+use serde::Serialize;
+
+use super::engine::Engine;
+
+/// A trait representing a data format into which `SerializerEngine` serializes the context values.
+pub trait Format {
+    /// The type of serialized output.
+    type Body: ResBody;
+
+    /// The error type which will be returned from `serialize`.
+    type Error: Into<failure::Error>;
+
+    /// Returns the value of content-type of the serialized output.
+    fn content_type(&self) -> HeaderValue;
+
+    /// Serializes the given value.
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Self::Body, Self::Error>;
+}
+
+/// A template engine which serializes the context values into the specified format,
+/// instead of rendering a template.
+///
+/// The engines for the supported formats are available as the aliases of this type
+/// (e.g. `JsonEngine`).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SerializerEngine<F> {
+    format: F,
+}
+
+impl<F: Format> SerializerEngine<F> {
+    /// Create a new `SerializerEngine` which serializes the context values into `format`.
+    pub fn with_format(format: F) -> SerializerEngine<F> {
+        SerializerEngine { format }
+    }
+
+    /// Returns a reference to the format.
+    pub fn format(&self) -> &F {
+        &self.format
+    }
+}
+
+impl<F, CtxT> Engine<CtxT> for SerializerEngine<F>
+where
+    F: Format,
+    CtxT: Serialize,
+{
+    type Body = F::Body;
+    type Error = F::Error;
+
+    fn content_type_hint(&self, _: &CtxT) -> Option<HeaderValue> {
+        Some(self.format.content_type())
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        self.format.serialize(&value)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{Format, SerializerEngine};
+    use renderer::Renderer;
+
+    use finchers::prelude::*;
+    use finchers::test;
+    use serde::ser::{Error, Serialize, Serializer};
+
+    #[derive(Debug, Clone)]
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(S::Error::custom("unserializable"))
+        }
+    }
+
+    /// Checks that a value which fails to be serialized is reported as an internal server error.
+    pub(crate) fn assert_serialize_error<F>(format: F)
+    where
+        F: Format + Send + Sync + 'static,
+    {
+        let renderer = Renderer::new(SerializerEngine::with_format(format));
+        let mut runner = test::runner(endpoint::cloned(Unserializable).wrap(renderer));
+        let response = runner.perform("/").unwrap();
+        assert_eq!(response.status().as_u16(), 500);
+    }
+}
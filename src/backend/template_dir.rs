@@ -0,0 +1,270 @@
+#![cfg(any(feature = "use-tera", feature = "use-handlebars"))]
+
+use failure::{Backtrace, Fail};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::reload::walk_dir;
+
+/// A builder which loads all templates under a directory into a template registry.
+///
+/// Each file is registered by its `/`-separated path relative to the directory,
+/// with the optional prefix prepended (e.g. `prefix("mail/")` registers
+/// `welcome.html` as `mail/welcome.html`). Hidden files are skipped.
+///
+/// The registry is created by `load_tera` or `load_handlebars`, and the
+/// escaping and helpers can be configured on the returned registry.
+/// All templates are parsed even if some of them fail, and the failures are
+/// reported together by `TemplateDirError`.
+#[derive(Debug, Clone)]
+pub struct TemplateDir {
+    dir: PathBuf,
+    extensions: Vec<String>,
+    prefix: String,
+}
+
+impl TemplateDir {
+    /// Create a new `TemplateDir` which loads the templates from `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> TemplateDir {
+        TemplateDir {
+            dir: dir.into(),
+            extensions: vec![],
+            prefix: String::new(),
+        }
+    }
+
+    /// Adds an extension of the files to be loaded, without the leading dot.
+    ///
+    /// If no extension is added, all files in the directory are loaded.
+    pub fn extension(mut self, extension: impl Into<String>) -> TemplateDir {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    /// Sets the prefix prepended to the names of templates.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> TemplateDir {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Returns the path of the template directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes the Rust code which embeds all templates in the directory into `out_file`.
+    ///
+    /// This method is intended to be called from build scripts. The generated file
+    /// contains an expression of `EmbeddedTemplates`, and can be included as follows:
+    ///
+    /// ```ignore
+    /// static TEMPLATES: EmbeddedTemplates = include!(concat!(env!("OUT_DIR"), "/templates.rs"));
+    /// ```
+    ///
+    /// The templates can be parse-checked at build time by calling `load_tera` or
+    /// `load_handlebars` before this method, so that syntax errors fail the build.
+    /// See `examples/embed` for a complete build script, whose test compiles the
+    /// generated file and renders the embedded templates.
+    pub fn embed(&self, out_file: impl AsRef<Path>) -> Result<(), TemplateDirError> {
+        let templates = self.read_templates()?;
+        let mut code = String::from("::finchers_template::backend::EmbeddedTemplates::new(&[\n");
+        for t in &templates {
+            let path = match t.path {
+                Some(ref path) => path.canonicalize().map_err(TemplateDirError::io)?,
+                None => continue,
+            };
+            code.push_str(&format!("    ({:?}, include_str!({:?})),\n", t.name, path));
+        }
+        code.push_str("])\n");
+        fs::write(out_file, code).map_err(TemplateDirError::io)?;
+
+        // Cargo scans the whole directory for modifications.
+        println!("cargo:rerun-if-changed={}", self.dir.display());
+        Ok(())
+    }
+
+    /// Reads the contents of all templates to be loaded, paired with their names.
+    pub(crate) fn read_templates(&self) -> Result<Vec<TemplateFile>, TemplateDirError> {
+        let mut templates = vec![];
+        for (path, name) in walk_dir(&self.dir).map_err(TemplateDirError::io)? {
+            if !self.extensions.is_empty() {
+                let matched = match path.extension().and_then(|ext| ext.to_str()) {
+                    Some(ext) => self.extensions.iter().any(|e| e == ext),
+                    None => false,
+                };
+                if !matched {
+                    continue;
+                }
+            }
+            let content = fs::read_to_string(&path).map_err(TemplateDirError::io)?;
+            templates.push(TemplateFile {
+                name: format!("{}{}", self.prefix, name),
+                path: Some(path),
+                content,
+            });
+        }
+        Ok(templates)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TemplateFile {
+    pub(crate) name: String,
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) content: String,
+}
+
+/// A template which failed to be loaded by `TemplateDir`.
+#[derive(Debug, Clone)]
+pub struct TemplateError {
+    name: Option<String>,
+    path: Option<PathBuf>,
+    message: String,
+}
+
+impl TemplateError {
+    pub(crate) fn new(
+        name: Option<String>,
+        path: Option<PathBuf>,
+        message: String,
+    ) -> TemplateError {
+        TemplateError {
+            name,
+            path,
+            message,
+        }
+    }
+
+    /// Returns the name of the template, if the error is specific to a template.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the path of the template file, if the error is specific to a template.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the description of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(ref name) => write!(f, "{}: {}", name, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// The error type returned when loading a template directory fails.
+#[derive(Debug)]
+pub struct TemplateDirError {
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    Io(io::Error),
+    Templates(Vec<TemplateError>),
+}
+
+impl TemplateDirError {
+    fn io(err: io::Error) -> TemplateDirError {
+        TemplateDirError {
+            kind: ErrorKind::Io(err),
+        }
+    }
+
+    pub(crate) fn templates(errors: Vec<TemplateError>) -> TemplateDirError {
+        TemplateDirError {
+            kind: ErrorKind::Templates(errors),
+        }
+    }
+
+    /// Returns the templates which failed to be loaded.
+    ///
+    /// The returned slice is empty when the directory itself cannot be read.
+    pub fn errors(&self) -> &[TemplateError] {
+        match self.kind {
+            ErrorKind::Templates(ref errors) => errors,
+            ErrorKind::Io(..) => &[],
+        }
+    }
+}
+
+impl fmt::Display for TemplateDirError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Io(ref err) => write!(f, "failed to read the template directory: {}", err),
+            ErrorKind::Templates(ref errors) => {
+                write!(f, "{} template(s) failed to load", errors.len())?;
+                for err in errors {
+                    write!(f, "\n  {}", err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Fail for TemplateDirError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match self.kind {
+            ErrorKind::Io(ref err) => Some(err),
+            ErrorKind::Templates(..) => None,
+        }
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TemplateDir;
+    use backend::reload::tests::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_read_templates() {
+        let dir = temp_dir("template-dir");
+        fs::create_dir_all(dir.join("layouts")).unwrap();
+        fs::write(dir.join("index.html"), "index").unwrap();
+        fs::write(dir.join("layouts/base.html"), "base").unwrap();
+        fs::write(dir.join("notes.txt"), "notes").unwrap();
+
+        let templates = TemplateDir::new(&dir)
+            .extension("html")
+            .prefix("app/")
+            .read_templates()
+            .unwrap();
+        let names: Vec<_> = templates.iter().map(|t| &t.name[..]).collect();
+        assert_eq!(names, vec!["app/index.html", "app/layouts/base.html"]);
+        assert_eq!(templates[1].content, "base");
+
+        let templates = TemplateDir::new(&dir).read_templates().unwrap();
+        assert_eq!(templates.len(), 3);
+
+        let out_file = dir.join("templates.rs");
+        TemplateDir::new(&dir)
+            .extension("html")
+            .embed(&out_file)
+            .unwrap();
+        let code = fs::read_to_string(&out_file).unwrap();
+        assert!(code.starts_with("::finchers_template::backend::EmbeddedTemplates::new(&["));
+        assert!(code.contains("(\"index.html\", include_str!("));
+        assert!(code.contains("(\"layouts/base.html\", include_str!("));
+        assert!(!code.contains("notes.txt"));
+
+        assert!(TemplateDir::new(dir.join("missing"))
+            .read_templates()
+            .is_err());
+    }
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "serde-context")]
+
+use http::header::HeaderValue;
+use mime_guess::from_path;
+use serde::{Serialize, Serializer};
+use std::borrow::Cow;
+
+/// A trait representing a context value which chooses the template used for rendering itself.
+///
+/// This trait is used by the engines which select the template at render time,
+/// such as `DynamicTeraEngine` and `DynamicHandlebarsEngine`.
+pub trait TemplateName {
+    /// Returns the name of template used for rendering this value.
+    fn template_name(&self) -> &str;
+}
+
+impl<T: TemplateName + ?Sized> TemplateName for &T {
+    fn template_name(&self) -> &str {
+        (**self).template_name()
+    }
+}
+
+impl<T: TemplateName + ?Sized> TemplateName for Box<T> {
+    fn template_name(&self) -> &str {
+        (**self).template_name()
+    }
+}
+
+/// A pair of a template name and a context value.
+///
+/// The value is serialized as the inner context, so the template name
+/// is not visible from the template.
+#[derive(Debug, Clone, Hash)]
+pub struct Named<CtxT> {
+    name: Cow<'static, str>,
+    ctx: CtxT,
+}
+
+impl<CtxT> Named<CtxT> {
+    /// Create a new `Named` from the specified template name and context value.
+    pub fn new(name: impl Into<Cow<'static, str>>, ctx: CtxT) -> Named<CtxT> {
+        Named {
+            name: name.into(),
+            ctx,
+        }
+    }
+
+    /// Returns a reference to the inner context value.
+    pub fn context(&self) -> &CtxT {
+        &self.ctx
+    }
+
+    /// Consumes itself and returns the inner context value.
+    pub fn into_context(self) -> CtxT {
+        self.ctx
+    }
+}
+
+impl<CtxT> TemplateName for Named<CtxT> {
+    fn template_name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<CtxT: Serialize> Serialize for Named<CtxT> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.ctx.serialize(serializer)
+    }
+}
+
+pub(crate) fn guess_content_type(name: &str) -> Option<HeaderValue> {
+    from_path(name).first().map(|mime| {
+        mime.as_ref()
+            .parse()
+            .expect("should be a valid header value")
+    })
+}
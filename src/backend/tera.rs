@@ -1,13 +1,19 @@
 #![cfg(feature = "use-tera")]
 
-use failure::SyncFailure;
+use failure::{self, SyncFailure};
 use http::header::HeaderValue;
-use mime_guess::guess_mime_type_opt;
 use serde::Serialize;
 use std::borrow::Cow;
-use tera::Tera;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tera::ast::Node;
+use tera::{Template, Tera};
 
-use super::engine::{Engine, EngineImpl};
+use super::embed::EmbeddedTemplates;
+use super::engine::{Engine, HasTemplate, TemplateNotFound, Validate};
+use super::reload::Reloadable;
+use super::template_dir::{TemplateDir, TemplateDirError, TemplateError, TemplateFile};
+use super::template_name::{guess_content_type, TemplateName};
 use renderer::Renderer;
 
 pub trait AsTera {
@@ -38,13 +44,122 @@ impl<T: AsTera> AsTera for ::std::sync::Arc<T> {
     }
 }
 
+/// A trait representing a source of `Tera` which is accessed only within a closure.
+///
+/// This trait is implemented for all types implementing `AsTera`, and for
+/// `Reloadable<Tera>` whose registry may be replaced between two renderings.
+pub trait WithTera {
+    fn with_tera<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Tera) -> R;
+}
+
+impl<T: AsTera> WithTera for T {
+    fn with_tera<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Tera) -> R,
+    {
+        f(self.as_tera())
+    }
+}
+
+impl WithTera for Reloadable<Tera> {
+    fn with_tera<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Tera) -> R,
+    {
+        f(&self.registry())
+    }
+}
+
+impl WithTera for ::std::sync::Arc<Reloadable<Tera>> {
+    fn with_tera<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Tera) -> R,
+    {
+        (**self).with_tera(f)
+    }
+}
+
+impl Reloadable<Tera> {
+    /// Create a `Reloadable<Tera>` which registers all files under `dir` by their relative paths.
+    pub fn tera(dir: impl Into<PathBuf>) -> Result<Reloadable<Tera>, failure::Error> {
+        Reloadable::new(dir, load_tera_dir)
+    }
+}
+
+fn load_tera_dir(dir: &Path) -> Result<Tera, failure::Error> {
+    Ok(TemplateDir::new(dir).load_tera()?)
+}
+
+impl TemplateDir {
+    /// Creates a `Tera` which registers all templates in the directory.
+    pub fn load_tera(&self) -> Result<Tera, TemplateDirError> {
+        load_tera_templates(self.read_templates()?)
+    }
+}
+
+impl EmbeddedTemplates {
+    /// Creates a `Tera` which registers all embedded templates.
+    pub fn load_tera(&self) -> Result<Tera, TemplateDirError> {
+        load_tera_templates(self.to_files())
+    }
+}
+
+pub(crate) fn load_tera_templates(templates: Vec<TemplateFile>) -> Result<Tera, TemplateDirError> {
+    // Parse each template individually to collect all syntax errors.
+    let errors: Vec<_> = templates
+        .iter()
+        .filter_map(|t| {
+            let path = t.path.as_ref().map(|p| p.to_string_lossy().into_owned());
+            Template::new(&t.name, path, &t.content).err().map(|err| {
+                TemplateError::new(
+                    Some(t.name.clone()),
+                    t.path.clone(),
+                    tera_error_message(&err),
+                )
+            })
+        })
+        .collect();
+    if !errors.is_empty() {
+        return Err(TemplateDirError::templates(errors));
+    }
+
+    let mut tera = Tera::default();
+    tera.add_raw_templates(templates.iter().map(|t| (&*t.name, &*t.content)).collect())
+        .map_err(|err| {
+            TemplateDirError::templates(vec![TemplateError::new(
+                None,
+                None,
+                tera_error_message(&err),
+            )])
+        })?;
+    Ok(tera)
+}
+
+/// Joins the messages in the error chain of Tera.
+fn tera_error_message(err: &::tera::Error) -> String {
+    err.iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
 pub fn tera<T>(tera: T, name: impl Into<Cow<'static, str>>) -> Renderer<TeraEngine<T>>
 where
-    T: AsTera,
+    T: WithTera,
 {
     Renderer::new(TeraEngine::new(tera, name))
 }
 
+/// Create a `Renderer` which selects the template by the name provided from the context value.
+pub fn tera_dynamic<T>(tera: T) -> Renderer<DynamicTeraEngine<T>>
+where
+    T: WithTera,
+{
+    Renderer::new(DynamicTeraEngine::new(tera))
+}
+
 #[derive(Debug)]
 pub struct TeraEngine<T> {
     tera: T,
@@ -54,15 +169,11 @@ pub struct TeraEngine<T> {
 
 impl<T> TeraEngine<T>
 where
-    T: AsTera,
+    T: WithTera,
 {
     pub fn new(tera: T, name: impl Into<Cow<'static, str>>) -> TeraEngine<T> {
         let name = name.into();
-        let content_type = guess_mime_type_opt(&*name).map(|mime| {
-            mime.as_ref()
-                .parse()
-                .expect("should be a valid header value")
-        });
+        let content_type = guess_content_type(&name);
         TeraEngine {
             tera,
             name,
@@ -72,19 +183,15 @@ where
 
     pub fn set_template_name(&mut self, name: impl Into<Cow<'static, str>>) {
         self.name = name.into();
-        if let Some(value) = guess_mime_type_opt(&*self.name)
-            .map(|s| s.as_ref().parse().expect("should be a valid header name"))
-        {
+        if let Some(value) = guess_content_type(&self.name) {
             self.content_type = Some(value);
         }
     }
 }
 
-impl<T, CtxT: Serialize> Engine<CtxT> for TeraEngine<T> where T: AsTera {}
-
-impl<T, CtxT: Serialize> EngineImpl<CtxT> for TeraEngine<T>
+impl<T, CtxT: Serialize> Engine<CtxT> for TeraEngine<T>
 where
-    T: AsTera,
+    T: WithTera,
 {
     type Body = String;
     type Error = SyncFailure<::tera::Error>;
@@ -95,12 +202,160 @@ where
 
     fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
         self.tera
-            .as_tera()
-            .render(&self.name, &value)
+            .with_tera(|tera| tera.render(&self.name, &value))
+            .map_err(SyncFailure::new)
+    }
+}
+
+impl<T> Validate for TeraEngine<T>
+where
+    T: WithTera,
+{
+    fn validate(&self) -> Result<(), failure::Error> {
+        self.tera.with_tera(|tera| {
+            let mut visited = HashSet::new();
+            validate_template(tera, &self.name, None, &mut visited)
+        })
+    }
+}
+
+impl<T, CtxT> HasTemplate<CtxT> for TeraEngine<T>
+where
+    T: WithTera,
+{
+    fn has_template(&self, _: &CtxT) -> bool {
+        self.tera.with_tera(|tera| has_template(tera, &self.name))
+    }
+}
+
+/// Returns `true` if the template and all templates referenced from it are registered.
+fn has_template(tera: &Tera, name: &str) -> bool {
+    validate_template(tera, name, None, &mut HashSet::new()).is_ok()
+}
+
+/// Checks that the template and all templates referenced from it are registered.
+fn validate_template<'a>(
+    tera: &'a Tera,
+    name: &'a str,
+    referrer: Option<&str>,
+    visited: &mut HashSet<&'a str>,
+) -> Result<(), failure::Error> {
+    if !visited.insert(name) {
+        return Ok(());
+    }
+    let template = tera.templates.get(name).ok_or_else(|| match referrer {
+        Some(referrer) => failure::format_err!(
+            "the template `{}` referenced from `{}` is not registered",
+            name,
+            referrer
+        ),
+        None => TemplateNotFound::new(name).into(),
+    })?;
+
+    let mut references = vec![];
+    references.extend(template.parent.as_ref().map(|p| &p[..]));
+    references.extend(
+        template
+            .imported_macro_files
+            .iter()
+            .map(|(file, _)| &file[..]),
+    );
+    collect_includes(&template.ast, &mut references);
+    for blocks in template.blocks_definitions.values() {
+        for (_, block) in blocks {
+            collect_includes(&block.body, &mut references);
+        }
+    }
+    for reference in references {
+        validate_template(tera, reference, Some(name), visited)?;
+    }
+    Ok(())
+}
+
+fn collect_includes<'a>(nodes: &'a [Node], includes: &mut Vec<&'a str>) {
+    for node in nodes {
+        match *node {
+            Node::Include(_, ref name) => includes.push(name),
+            Node::MacroDefinition(_, ref def, _) => collect_includes(&def.body, includes),
+            Node::FilterSection(_, ref section, _) => collect_includes(&section.body, includes),
+            Node::Block(_, ref block, _) => collect_includes(&block.body, includes),
+            Node::Forloop(_, ref forloop, _) => collect_includes(&forloop.body, includes),
+            Node::If(ref cond, _) => {
+                for (_, _, body) in &cond.conditions {
+                    collect_includes(body, includes);
+                }
+                if let Some((_, ref body)) = cond.otherwise {
+                    collect_includes(body, includes);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A template engine which renders the template chosen by the context value.
+#[derive(Debug)]
+pub struct DynamicTeraEngine<T> {
+    tera: T,
+}
+
+impl<T> DynamicTeraEngine<T>
+where
+    T: WithTera,
+{
+    pub fn new(tera: T) -> DynamicTeraEngine<T> {
+        DynamicTeraEngine { tera }
+    }
+}
+
+impl<T, CtxT> Engine<CtxT> for DynamicTeraEngine<T>
+where
+    T: WithTera,
+    CtxT: Serialize + TemplateName,
+{
+    type Body = String;
+    type Error = SyncFailure<::tera::Error>;
+
+    fn content_type_hint(&self, value: &CtxT) -> Option<HeaderValue> {
+        guess_content_type(value.template_name())
+    }
+
+    fn render(&self, value: CtxT) -> Result<Self::Body, Self::Error> {
+        self.tera
+            .with_tera(|tera| tera.render(value.template_name(), &value))
             .map_err(SyncFailure::new)
     }
 }
 
+impl<T> Validate for DynamicTeraEngine<T>
+where
+    T: WithTera,
+{
+    /// Checks that all templates referenced from the registered templates are registered.
+    fn validate(&self) -> Result<(), failure::Error> {
+        self.tera.with_tera(|tera| {
+            let mut visited = HashSet::new();
+            let mut names: Vec<_> = tera.templates.keys().collect();
+            names.sort();
+            for name in names {
+                validate_template(tera, name, None, &mut visited)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<T, CtxT> HasTemplate<CtxT> for DynamicTeraEngine<T>
+where
+    T: WithTera,
+    CtxT: TemplateName,
+{
+    fn has_template(&self, value: &CtxT) -> bool {
+        self.tera
+            .with_tera(|tera| has_template(tera, value.template_name()))
+    }
+}
+
 #[test]
 fn test_tera() {
     use std::sync::Arc;
@@ -121,3 +376,310 @@ fn test_tera() {
     let body = engine.render(value).unwrap();
     assert_eq!(body, "Alice");
 }
+
+#[test]
+fn test_tera_dynamic() {
+    use super::template_name::Named;
+
+    #[derive(Debug, Serialize)]
+    #[serde(untagged)]
+    enum Context {
+        List { items: Vec<String> },
+        Empty {},
+    }
+
+    impl TemplateName for Context {
+        fn template_name(&self) -> &str {
+            match self {
+                Context::List { .. } => "list.html",
+                Context::Empty {} => "empty.txt",
+            }
+        }
+    }
+
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        (
+            "list.html",
+            "{% for item in items %}{{ item }};{% endfor %}",
+        ),
+        ("empty.txt", "empty"),
+        ("pages/about.html", "About {{ name }}"),
+    ])
+    .unwrap();
+
+    let engine = DynamicTeraEngine::new(tera);
+
+    let value = Context::List {
+        items: vec!["Alice".into(), "Bob".into()],
+    };
+    assert_matches!(
+        engine.content_type_hint(&value),
+        Some(ref h) if h == "text/html"
+    );
+    assert_eq!(engine.render(value).unwrap(), "Alice;Bob;");
+
+    let value = Context::Empty {};
+    assert_matches!(
+        engine.content_type_hint(&value),
+        Some(ref h) if h == "text/plain"
+    );
+    assert_eq!(engine.render(value).unwrap(), "empty");
+
+    #[derive(Debug, Serialize)]
+    struct Page {
+        name: String,
+    }
+    let value = Named::new(
+        format!("pages/{}.html", "about"),
+        Page {
+            name: "Alice".into(),
+        },
+    );
+    assert_eq!(engine.render(value).unwrap(), "About Alice");
+}
+
+#[test]
+fn test_tera_reloadable() {
+    use super::reload::tests::temp_dir;
+    use std::fs;
+    use std::time::Duration;
+
+    #[derive(Debug, Serialize)]
+    struct Context {
+        name: String,
+    }
+
+    let dir = temp_dir("tera-reloadable");
+    fs::write(dir.join("index.html"), "Hello, {{ name }}.").unwrap();
+
+    let tera = Reloadable::tera(&dir)
+        .unwrap()
+        .poll_interval(Duration::from_millis(0));
+    let engine = TeraEngine::new(tera, "index.html");
+    let render = || {
+        engine
+            .render(Context {
+                name: "Alice".into(),
+            })
+            .unwrap()
+    };
+    assert_eq!(render(), "Hello, Alice.");
+
+    fs::create_dir_all(dir.join("layouts")).unwrap();
+    fs::write(
+        dir.join("layouts/base.html"),
+        "[{% block body %}{% endblock %}]",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("index.html"),
+        "{% extends \"layouts/base.html\" %}{% block body %}Bye, {{ name }}.{% endblock %}",
+    )
+    .unwrap();
+    assert_eq!(render(), "[Bye, Alice.]");
+    assert!(engine.tera.take_error().is_none());
+
+    // a broken template is reported, and the last good version is kept.
+    fs::write(dir.join("broken.html"), "{% if %}").unwrap();
+    assert_eq!(render(), "[Bye, Alice.]");
+    assert!(engine.tera.take_error().is_some());
+    assert!(engine.tera.reload().is_err());
+
+    fs::remove_file(dir.join("broken.html")).unwrap();
+    assert_eq!(render(), "[Bye, Alice.]");
+    assert!(engine.tera.take_error().is_none());
+    assert!(engine.tera.reload().is_ok());
+}
+
+#[test]
+fn test_tera_with_globals() {
+    use finchers::prelude::*;
+    use finchers::test;
+    use globals::Globals;
+
+    #[derive(Debug, Serialize)]
+    struct Context {
+        name: String,
+    }
+
+    let mut registry = Tera::default();
+    registry
+        .add_raw_template("index.html", "{{ name }} (v{{ globals.version }})")
+        .unwrap();
+
+    let mut runner = test::runner({
+        endpoint::syntax::verb::get()
+            .map(|| Context {
+                name: "Alice".into(),
+            })
+            .wrap(tera(registry, "index.html").with_globals(Globals::new().value("version", "1.0")))
+    });
+    let response = runner.perform("/").unwrap();
+    assert_eq!(response.body().to_utf8().unwrap(), "Alice (v1.0)");
+}
+
+#[test]
+fn test_tera_template_dir() {
+    use super::reload::tests::temp_dir;
+    use std::fs;
+
+    let dir = temp_dir("tera-template-dir");
+    fs::create_dir_all(dir.join("layouts")).unwrap();
+    fs::write(
+        dir.join("layouts/base.html"),
+        "[{% block body %}{% endblock %}]",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("index.html"),
+        "{% extends \"app/layouts/base.html\" %}{% block body %}{{ name }}{% endblock %}",
+    )
+    .unwrap();
+    fs::write(dir.join("README.md"), "{% if %}").unwrap();
+
+    let dir = TemplateDir::new(&dir).extension("html").prefix("app/");
+    let tera = dir.load_tera().unwrap();
+    let body = tera
+        .render("app/index.html", &::serde_json::json!({ "name": "Alice" }))
+        .unwrap();
+    assert_eq!(body, "[Alice]");
+
+    fs::write(dir.dir().join("broken.html"), "{% if %}").unwrap();
+    fs::write(dir.dir().join("broken2.html"), "{{ }}").unwrap();
+    let err = dir.load_tera().unwrap_err();
+    let names: Vec<_> = err.errors().iter().map(|e| e.name()).collect();
+    assert_eq!(
+        names,
+        vec![Some("app/broken.html"), Some("app/broken2.html")]
+    );
+}
+
+#[test]
+fn test_tera_embedded() {
+    static TEMPLATES: EmbeddedTemplates = EmbeddedTemplates::new(&[
+        ("base.html", "[{% block body %}{% endblock %}]"),
+        (
+            "index.html",
+            "{% extends \"base.html\" %}{% block body %}{{ name }}{% endblock %}",
+        ),
+    ]);
+    let tera = TEMPLATES.load_tera().unwrap();
+    let body = tera
+        .render("index.html", &::serde_json::json!({ "name": "Alice" }))
+        .unwrap();
+    assert_eq!(body, "[Alice]");
+
+    static BROKEN: EmbeddedTemplates = EmbeddedTemplates::new(&[("broken.html", "{% if %}")]);
+    let err = BROKEN.load_tera().unwrap_err();
+    assert_eq!(err.errors()[0].name(), Some("broken.html"));
+    assert_eq!(err.errors()[0].path(), None);
+}
+
+#[test]
+fn test_tera_validate() {
+    let mut registry = Tera::default();
+    registry
+        .add_raw_templates(vec![
+            ("base.html", "{% block body %}{% endblock %}"),
+            (
+                "index.html",
+                "{% extends \"base.html\" %}{% block body %}\
+                 {% if a %}{% include \"nav.html\" %}{% endif %}{% endblock %}",
+            ),
+            ("nav.html", "{% include \"missing.html\" %}"),
+            ("about.html", "{% extends \"base.html\" %}"),
+        ])
+        .unwrap();
+    let registry = ::std::sync::Arc::new(registry);
+
+    assert!(tera(registry.clone(), "about.html").validate().is_ok());
+    assert!(tera(registry.clone(), "about.html").validated().is_ok());
+
+    let err = tera(registry.clone(), "index.html").validate().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "the template `missing.html` referenced from `nav.html` is not registered"
+    );
+
+    let err = tera(registry.clone(), "contact.html")
+        .validate()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "the template `contact.html` is not registered"
+    );
+}
+
+#[test]
+fn test_tera_dynamic_validate() {
+    let mut registry = Tera::default();
+    registry
+        .add_raw_templates(vec![
+            ("index.html", "{% include \"nav.html\" %}"),
+            ("nav.html", "nav"),
+        ])
+        .unwrap();
+    assert!(tera_dynamic(registry).validate().is_ok());
+
+    let mut registry = Tera::default();
+    registry
+        .add_raw_templates(vec![
+            ("index.html", "{% include \"nav.html\" %}"),
+            ("about.html", "{% include \"missing.html\" %}"),
+            ("nav.html", "nav"),
+        ])
+        .unwrap();
+    let err = tera_dynamic(registry).validate().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "the template `missing.html` referenced from `about.html` is not registered"
+    );
+}
+
+#[test]
+fn test_tera_fallback() {
+    use super::combinator::FallbackEngine;
+    use super::template_name::Named;
+    use either::Either;
+    use std::sync::Arc;
+
+    #[derive(Debug, Serialize)]
+    struct Context {
+        name: String,
+    }
+
+    let mut registry = Tera::default();
+    registry
+        .add_raw_templates(vec![
+            ("themes/dark/index.html", "Dark {{ name }}"),
+            ("themes/broken/index.html", "{% include \"missing.html\" %}"),
+            ("default.html", "Default {{ name }}"),
+        ])
+        .unwrap();
+    let registry = Arc::new(registry);
+
+    let engine = FallbackEngine::new(
+        DynamicTeraEngine::new(registry.clone()),
+        TeraEngine::new(registry, "default.html"),
+    );
+    let render = |theme: &str| {
+        let value = Named::new(
+            format!("themes/{}/index.html", theme),
+            Context {
+                name: "Alice".into(),
+            },
+        );
+        match engine.render(value).unwrap() {
+            Either::Left(body) | Either::Right(body) => body,
+        }
+    };
+    assert_eq!(render("dark"), "Dark Alice");
+    assert_eq!(render("light"), "Default Alice");
+    // the template referencing a missing template is not used.
+    assert_eq!(render("broken"), "Default Alice");
+    assert_eq!(
+        engine.validate().unwrap_err().to_string(),
+        "the template `missing.html` referenced from `themes/broken/index.html` is not registered"
+    );
+}
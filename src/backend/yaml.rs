@@ -0,0 +1,72 @@
+#![cfg(feature = "use-yaml")]
+
+use http::header::HeaderValue;
+use serde::Serialize;
+
+use super::serializer::{Format, SerializerEngine};
+use renderer::Renderer;
+
+/// Create a `Renderer` which serializes the context values into YAML.
+pub fn yaml() -> Renderer<YamlEngine> {
+    Renderer::new(YamlEngine::default())
+}
+
+/// A template engine which serializes the context values into YAML.
+pub type YamlEngine = SerializerEngine<Yaml>;
+
+/// The YAML format.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Yaml {
+    _priv: (),
+}
+
+impl Format for Yaml {
+    type Body = String;
+    type Error = ::serde_yaml::Error;
+
+    fn content_type(&self) -> HeaderValue {
+        HeaderValue::from_static("application/yaml")
+    }
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Self::Body, Self::Error> {
+        ::serde_yaml::to_string(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Yaml, YamlEngine};
+    use backend::engine::Engine;
+    use backend::serializer::tests::assert_serialize_error;
+
+    use serde::Serialize;
+
+    #[test]
+    fn test_yaml() {
+        #[derive(Debug, Serialize)]
+        struct Context {
+            name: String,
+            items: Vec<u32>,
+        }
+
+        let value = Context {
+            name: "Alice".into(),
+            items: vec![1, 2],
+        };
+
+        let engine = YamlEngine::default();
+        assert_matches!(
+            engine.content_type_hint(&value),
+            Some(ref h) if h == "application/yaml"
+        );
+        assert_eq!(
+            engine.render(value).unwrap(),
+            "name: Alice\nitems:\n- 1\n- 2\n"
+        );
+    }
+
+    #[test]
+    fn test_yaml_error() {
+        assert_serialize_error(Yaml::default());
+    }
+}
@@ -0,0 +1,477 @@
+use finchers::endpoint::wrapper::Wrapper;
+use finchers::endpoint::{ApplyContext, ApplyResult, Endpoint};
+use finchers::error;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use futures::sync::oneshot;
+use futures::task::{self, Task};
+use futures::{Async, Future, Poll};
+use http::{Response, StatusCode};
+
+use backend::engine::Engine;
+use renderer::Renderer;
+
+pub(crate) type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A pool of worker threads used for rendering the templates.
+///
+/// The number of renderings submitted to the pool at the same time is limited
+/// to `pool_size + queue_size`. Once the pool is saturated, the futures
+/// waiting for the pool are suspended until one of the running renderings
+/// is completed.
+///
+/// The values of `RenderPool` are cheaply cloneable, and the worker threads
+/// are stopped when all of them are dropped.
+#[derive(Debug, Clone)]
+pub struct RenderPool {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    shared: Arc<Shared>,
+    capacity: usize,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+struct State {
+    jobs: VecDeque<Job>,
+    running: usize,
+    waiters: Vec<Task>,
+    shutdown: bool,
+}
+
+impl fmt::Debug for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("Shared")
+            .field("queued", &state.jobs.len())
+            .field("running", &state.running)
+            .field("waiters", &state.waiters.len())
+            .field("shutdown", &state.shutdown)
+            .finish()
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn run_worker(&self) {
+        loop {
+            let job = {
+                let mut state = self.lock();
+                loop {
+                    if let Some(job) = state.jobs.pop_front() {
+                        break job;
+                    }
+                    if state.shutdown {
+                        return;
+                    }
+                    state = self
+                        .condvar
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            };
+
+            // A panic in the engine must not kill the worker, otherwise the slot
+            // occupied by the job would never be released. The sender of the result
+            // is dropped while unwinding, so the rendering is reported as canceled.
+            let _ = panic::catch_unwind(AssertUnwindSafe(job));
+
+            let waiters = {
+                let mut state = self.lock();
+                state.running -= 1;
+                state.waiters.split_off(0)
+            };
+            for waiter in waiters {
+                waiter.notify();
+            }
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.condvar.notify_all();
+    }
+}
+
+impl RenderPool {
+    /// Create a new `RenderPool` with `pool_size` worker threads.
+    ///
+    /// `queue_size` is the number of renderings which can wait for a free
+    /// worker thread before the pool is regarded as saturated.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `pool_size` is zero or a worker thread cannot be spawned.
+    pub fn new(pool_size: usize, queue_size: usize) -> RenderPool {
+        assert!(pool_size > 0, "the pool size must be greater than zero");
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                jobs: VecDeque::new(),
+                running: 0,
+                waiters: vec![],
+                shutdown: false,
+            }),
+            condvar: Condvar::new(),
+        });
+        for i in 0..pool_size {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name(format!("finchers-template-render-{}", i))
+                .spawn(move || shared.run_worker())
+                .expect("failed to spawn a worker thread");
+        }
+
+        RenderPool {
+            inner: Arc::new(Inner {
+                shared,
+                capacity: pool_size + queue_size,
+            }),
+        }
+    }
+
+    /// Submits a job to the pool if it is not saturated.
+    ///
+    /// Otherwise, the job is given back and the current task is notified
+    /// when the pool becomes available again.
+    pub(crate) fn poll_submit(&self, job: Job) -> Result<(), Job> {
+        let shared = &self.inner.shared;
+        let mut state = shared.lock();
+        if state.running >= self.inner.capacity {
+            if !state.waiters.iter().any(Task::will_notify_current) {
+                state.waiters.push(task::current());
+            }
+            return Err(job);
+        }
+        state.running += 1;
+        state.jobs.push_back(job);
+        drop(state);
+        shared.condvar.notify_one();
+        Ok(())
+    }
+}
+
+impl<Eng> Renderer<Eng> {
+    /// Converts this renderer into one which renders the values on the specified worker pool.
+    pub fn offload(self, pool: &RenderPool) -> BlockingRenderer<Eng> {
+        BlockingRenderer {
+            renderer: Arc::new(self),
+            pool: pool.clone(),
+        }
+    }
+}
+
+/// A renderer which renders the context values on a `RenderPool`.
+///
+/// The value of this type is created by `Renderer::offload`.
+#[derive(Debug)]
+pub struct BlockingRenderer<Eng> {
+    renderer: Arc<Renderer<Eng>>,
+    pool: RenderPool,
+}
+
+impl<'a, E, Eng, T> Wrapper<'a, E> for BlockingRenderer<Eng>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: Engine<T> + Send + Sync + 'static,
+    Eng::Body: Send + 'static,
+    T: Send + 'static,
+{
+    type Output = (Response<Eng::Body>,);
+    type Endpoint = BlockingRenderEndpoint<E, Eng, T>;
+
+    fn wrap(self, endpoint: E) -> Self::Endpoint {
+        BlockingRenderEndpoint {
+            endpoint,
+            renderer: self,
+            _marker: PhantomData,
+        }
+    }
+}
+
+pub struct BlockingRenderEndpoint<E, Eng, T> {
+    endpoint: E,
+    renderer: BlockingRenderer<Eng>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<E, Eng, T> fmt::Debug for BlockingRenderEndpoint<E, Eng, T>
+where
+    E: fmt::Debug,
+    Eng: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockingRenderEndpoint")
+            .field("endpoint", &self.endpoint)
+            .field("renderer", &self.renderer)
+            .finish()
+    }
+}
+
+impl<'a, E, Eng, T> Endpoint<'a> for BlockingRenderEndpoint<E, Eng, T>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: Engine<T> + Send + Sync + 'static,
+    Eng::Body: Send + 'static,
+    T: Send + 'static,
+{
+    type Output = (Response<Eng::Body>,);
+    type Future = BlockingRenderFuture<'a, E, Eng, T>;
+
+    fn apply(&'a self, cx: &mut ApplyContext<'_>) -> ApplyResult<Self::Future> {
+        Ok(BlockingRenderFuture {
+            state: RenderState::Endpoint(self.endpoint.apply(cx)?),
+            renderer: &self.renderer,
+        })
+    }
+}
+
+pub struct BlockingRenderFuture<'a, E: Endpoint<'a>, Eng: Engine<T>, T> {
+    state: RenderState<'a, E, Eng::Body>,
+    renderer: &'a BlockingRenderer<Eng>,
+}
+
+enum RenderState<'a, E: Endpoint<'a>, Bd> {
+    Endpoint(E::Future),
+    Submitting(Job, oneshot::Receiver<error::Result<Response<Bd>>>),
+    Rendering(oneshot::Receiver<error::Result<Response<Bd>>>),
+    Done,
+}
+
+impl<'a, E, Eng, T> fmt::Debug for BlockingRenderFuture<'a, E, Eng, T>
+where
+    E: Endpoint<'a>,
+    Eng: Engine<T> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            RenderState::Endpoint(..) => "Endpoint",
+            RenderState::Submitting(..) => "Submitting",
+            RenderState::Rendering(..) => "Rendering",
+            RenderState::Done => "Done",
+        };
+        f.debug_struct("BlockingRenderFuture")
+            .field("state", &state)
+            .field("renderer", &self.renderer)
+            .finish()
+    }
+}
+
+impl<'a, E, Eng, T> Future for BlockingRenderFuture<'a, E, Eng, T>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: Engine<T> + Send + Sync + 'static,
+    Eng::Body: Send + 'static,
+    T: Send + 'static,
+{
+    type Item = (Response<Eng::Body>,);
+    type Error = error::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            self.state = match ::std::mem::replace(&mut self.state, RenderState::Done) {
+                RenderState::Endpoint(mut future) => match future.poll()? {
+                    Async::Ready((value,)) => {
+                        let (tx, rx) = oneshot::channel();
+                        let renderer = self.renderer.renderer.clone();
+                        let job: Job = Box::new(move || {
+                            let _ = tx.send(renderer.render_response(value));
+                        });
+                        RenderState::Submitting(job, rx)
+                    }
+                    Async::NotReady => {
+                        self.state = RenderState::Endpoint(future);
+                        return Ok(Async::NotReady);
+                    }
+                },
+                RenderState::Submitting(job, rx) => match self.renderer.pool.poll_submit(job) {
+                    Ok(()) => RenderState::Rendering(rx),
+                    Err(job) => {
+                        self.state = RenderState::Submitting(job, rx);
+                        return Ok(Async::NotReady);
+                    }
+                },
+                RenderState::Rendering(mut rx) => match rx.poll() {
+                    Ok(Async::Ready(result)) => {
+                        return result.map(|response| Async::Ready((response,)))
+                    }
+                    Ok(Async::NotReady) => {
+                        self.state = RenderState::Rendering(rx);
+                        return Ok(Async::NotReady);
+                    }
+                    Err(oneshot::Canceled) => {
+                        return Err(error::err_msg(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "the rendering was canceled",
+                        ));
+                    }
+                },
+                RenderState::Done => panic!("cannot poll the future twice"),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RenderPool;
+    use backend::engine::Engine;
+    use renderer::Renderer;
+
+    use finchers::error;
+    use finchers::prelude::*;
+    use finchers::test;
+
+    use futures::executor::{self, Notify};
+    use futures::future;
+    use futures::sync::oneshot;
+    use futures::{Async, Future};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[derive(Debug)]
+    struct ThreadNameEngine;
+    impl Engine<String> for ThreadNameEngine {
+        type Body = String;
+        type Error = error::Never;
+        fn render(&self, value: String) -> Result<Self::Body, Self::Error> {
+            Ok(format!(
+                "{} from {}",
+                value,
+                thread::current().name().unwrap_or("<unnamed>")
+            ))
+        }
+    }
+
+    #[test]
+    fn test_offload() {
+        let pool = RenderPool::new(1, 0);
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and(endpoint::syntax::param::<String>())
+                .and(endpoint::syntax::eos())
+                .wrap(Renderer::new(ThreadNameEngine).offload(&pool))
+        });
+
+        let response = runner.perform("/Amaterasu").unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_matches!(
+            response.headers().get("content-type"),
+            Some(h) if h == "text/html; charset=utf-8"
+        );
+        assert_eq!(
+            response.body().to_utf8().unwrap(),
+            "Amaterasu from finchers-template-render-0"
+        );
+    }
+
+    #[test]
+    fn test_back_pressure() {
+        struct Flag(AtomicBool);
+        impl Notify for Flag {
+            fn notify(&self, _: usize) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let pool = RenderPool::new(1, 0);
+
+        // occupy the only worker thread until `release` is sent.
+        let (release, wait_release) = oneshot::channel::<()>();
+        let (started, wait_started) = oneshot::channel::<()>();
+        let mut blocker = Some(Box::new(move || {
+            let _ = started.send(());
+            let _ = wait_release.wait();
+        }) as super::Job);
+        let mut submit_blocker = executor::spawn(future::poll_fn(|| {
+            pool.poll_submit(blocker.take().unwrap())
+                .map(Async::Ready)
+                .map_err(|_| ())
+        }));
+        submit_blocker.wait_future().unwrap();
+        wait_started.wait().unwrap();
+
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        let (done, wait_done) = oneshot::channel::<()>();
+        let mut job = Some(Box::new(move || {
+            let _ = done.send(());
+        }) as super::Job);
+        let mut submit = executor::spawn(future::poll_fn(|| {
+            match pool.poll_submit(job.take().unwrap()) {
+                Ok(()) => Ok::<_, ()>(Async::Ready(())),
+                Err(returned) => {
+                    job = Some(returned);
+                    Ok(Async::NotReady)
+                }
+            }
+        }));
+
+        assert_eq!(submit.poll_future_notify(&flag, 0), Ok(Async::NotReady));
+        assert!(!flag.0.load(Ordering::SeqCst));
+
+        // polling the same task again does not register another waiter.
+        assert_eq!(submit.poll_future_notify(&flag, 0), Ok(Async::NotReady));
+        assert_eq!(pool.inner.shared.lock().waiters.len(), 1);
+
+        release.send(()).unwrap();
+        while !flag.0.load(Ordering::SeqCst) {
+            thread::yield_now();
+        }
+        assert_eq!(submit.poll_future_notify(&flag, 0), Ok(Async::Ready(())));
+        wait_done.wait().unwrap();
+    }
+
+    #[derive(Debug)]
+    struct PanicEngine;
+    impl Engine<String> for PanicEngine {
+        type Body = String;
+        type Error = error::Never;
+        fn render(&self, value: String) -> Result<Self::Body, Self::Error> {
+            panic!("failed to render {}", value)
+        }
+    }
+
+    #[test]
+    fn test_panic_in_engine() {
+        let pool = RenderPool::new(1, 0);
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and(endpoint::syntax::param::<String>())
+                .and(endpoint::syntax::eos())
+                .wrap(Renderer::new(PanicEngine).offload(&pool))
+        });
+
+        // the worker survives the panics and the pool does not run out of slots.
+        for _ in 0..3 {
+            let response = runner.perform("/Amaterasu").unwrap();
+            assert_eq!(response.status().as_u16(), 500);
+        }
+
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and(endpoint::syntax::param::<String>())
+                .and(endpoint::syntax::eos())
+                .wrap(Renderer::new(ThreadNameEngine).offload(&pool))
+        });
+        let response = runner.perform("/Amaterasu").unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
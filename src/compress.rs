@@ -0,0 +1,489 @@
+#![cfg(feature = "compression")]
+
+use finchers::endpoint::{ApplyContext, ApplyResult};
+use finchers::error;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::io::Write;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use brotli::CompressorWriter;
+use bytes::Bytes;
+use either::Either;
+use failure;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use futures::future::{self, FutureResult, Join};
+use futures::Future;
+use http::header;
+use http::header::HeaderValue;
+use http::Response;
+
+use renderer::{Renderer, Stage};
+
+const DEFAULT_MIN_SIZE: usize = 1024;
+const DEFAULT_CACHE_SIZE: usize = 64;
+
+/// The content codings supported by `CompressStage`, in the order of preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    const ALL: [Encoding; 3] = [Encoding::Brotli, Encoding::Gzip, Encoding::Deflate];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let mut buf = Vec::new();
+                {
+                    let mut encoder = CompressorWriter::new(&mut buf, 4096, 5, 22);
+                    encoder.write_all(data)?;
+                    encoder.flush()?;
+                }
+                Ok(buf)
+            }
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Chooses the content coding by the value of `Accept-Encoding`.
+///
+/// Returns `None` if the client does not accept any of the supported codings.
+fn negotiate_encoding(accept_encoding: &str) -> Option<Encoding> {
+    let mut qualities = HashMap::new();
+    let mut wildcard = None;
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';').map(|s| s.trim());
+        let coding = match parts.next() {
+            Some(coding) if !coding.is_empty() => coding.to_ascii_lowercase(),
+            _ => continue,
+        };
+        let q = parts
+            .filter_map(|param| {
+                let mut kv = param.splitn(2, '=');
+                match (kv.next(), kv.next()) {
+                    (Some(k), Some(v)) if k.trim().eq_ignore_ascii_case("q") => {
+                        v.trim().parse::<f32>().ok()
+                    }
+                    _ => None,
+                }
+            })
+            .next()
+            .map_or(1000, |q| (q.clamp(0.0, 1.0) * 1000.0) as u16);
+        if coding == "*" {
+            wildcard = Some(q);
+        } else {
+            qualities.insert(coding, q);
+        }
+    }
+
+    let mut best: Option<(Encoding, u16)> = None;
+    for &encoding in &Encoding::ALL {
+        let q = match qualities.get(encoding.as_str()).cloned().or(wildcard) {
+            Some(q) if q > 0 => q,
+            _ => continue,
+        };
+        match best {
+            Some((_, best_q)) if best_q >= q => {}
+            _ => best = Some((encoding, q)),
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+impl<Eng, S> Renderer<Eng, S> {
+    /// Adds a stage which compresses the rendered bodies.
+    ///
+    /// The content coding is chosen from `br`, `gzip` and `deflate` by the value of
+    /// `Accept-Encoding`, and the responses include `Vary: Accept-Encoding`.
+    /// The bodies smaller than `min_size` are sent without compression.
+    ///
+    /// The stage should be added before `etag`, so that the entity tags
+    /// differ between the encodings.
+    pub fn compress(self) -> Renderer<Eng, CompressStage<S>> {
+        self.with_stage(CompressStage::new)
+    }
+}
+
+impl<Eng, S> Renderer<Eng, CompressStage<S>> {
+    /// Sets the minimum size of bodies to be compressed, in bytes.
+    ///
+    /// The default value is 1024.
+    pub fn min_size(self, min_size: usize) -> Renderer<Eng, CompressStage<S>> {
+        self.with_stage(|stage| CompressStage { min_size, ..stage })
+    }
+
+    /// Sets the maximum number of compressed bodies kept for reuse.
+    ///
+    /// The default value is 64, and setting it to zero disables the reuse.
+    pub fn cache_size(self, capacity: usize) -> Renderer<Eng, CompressStage<S>> {
+        self.with_stage(|mut stage| {
+            {
+                let cache = stage
+                    .cache
+                    .get_mut()
+                    .unwrap_or_else(PoisonError::into_inner);
+                cache.capacity = capacity;
+                while cache.entries.len() > capacity {
+                    cache.entries.pop_front();
+                }
+            }
+            stage
+        })
+    }
+}
+
+/// A stage which compresses the rendered bodies according to `Accept-Encoding`.
+///
+/// The compressed variants of the recently rendered bodies are kept, so that
+/// identical bodies are not compressed again for each request.
+///
+/// The value of this type is added by `Renderer::compress`.
+#[derive(Debug)]
+pub struct CompressStage<S> {
+    stage: S,
+    min_size: usize,
+    cache: Mutex<CompressedCache>,
+}
+
+#[derive(Debug)]
+struct CompressedCache {
+    capacity: usize,
+    entries: VecDeque<CacheEntry>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    hash: u64,
+    encoding: Encoding,
+    body: Bytes,
+    compressed: Bytes,
+}
+
+impl CompressedCache {
+    fn get(&self, hash: u64, body: &[u8], encoding: Encoding) -> Option<Bytes> {
+        // The bodies are compared as well, since the distinct bodies may share the hash value.
+        self.entries
+            .iter()
+            .find(|entry| entry.hash == hash && entry.encoding == encoding && entry.body == body)
+            .map(|entry| entry.compressed.clone())
+    }
+
+    fn insert(&mut self, entry: CacheEntry) {
+        if self.capacity == 0 || self.get(entry.hash, &entry.body, entry.encoding).is_some() {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+impl<S> CompressStage<S> {
+    fn new(stage: S) -> CompressStage<S> {
+        CompressStage {
+            stage,
+            min_size: DEFAULT_MIN_SIZE,
+            cache: Mutex::new(CompressedCache {
+                capacity: DEFAULT_CACHE_SIZE,
+                entries: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn cache(&self) -> MutexGuard<'_, CompressedCache> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn compress_body(&self, body: &[u8], encoding: Encoding) -> error::Result<Bytes> {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let hash = hasher.finish();
+        if let Some(compressed) = self.cache().get(hash, body, encoding) {
+            return Ok(compressed);
+        }
+
+        // The lock is not held while compressing, so that the other requests are not blocked.
+        let compressed = encoding
+            .compress(body)
+            .map(Bytes::from)
+            .map_err(|err| error::Error::from(failure::Error::from(err)))?;
+        self.cache().insert(CacheEntry {
+            hash,
+            encoding,
+            body: Bytes::from(body),
+            compressed: compressed.clone(),
+        });
+        Ok(compressed)
+    }
+}
+
+impl<'a, Eng, S, T> Stage<'a, Eng, T> for CompressStage<S>
+where
+    S: Stage<'a, Eng, T>,
+    S::Body: AsRef<[u8]>,
+{
+    type Body = Either<S::Body, Bytes>;
+    type State = (Option<Encoding>, S::State);
+    type Prepare = Join<FutureResult<Option<Encoding>, error::Error>, S::Prepare>;
+
+    fn prepare(&'a self, cx: &mut ApplyContext<'_>) -> ApplyResult<Self::Prepare> {
+        let encoding = cx
+            .input()
+            .headers()
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|h| h.to_str().ok())
+            .and_then(negotiate_encoding);
+        let state = self.stage.prepare(cx)?;
+        Ok(future::ok(encoding).join(state))
+    }
+
+    fn render<R>(
+        &self,
+        renderer: &Renderer<Eng, R>,
+        (encoding, state): Self::State,
+        value: T,
+    ) -> error::Result<Response<Self::Body>> {
+        let response = self.stage.render(renderer, state, value)?;
+        let (mut parts, body) = response.into_parts();
+        parts
+            .headers
+            .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+        let encoding = match encoding {
+            Some(encoding) if body.as_ref().len() >= self.min_size => encoding,
+            _ => return Ok(Response::from_parts(parts, Either::Left(body))),
+        };
+        let compressed = self.compress_body(body.as_ref(), encoding)?;
+        parts.headers.insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.as_str()),
+        );
+        Ok(Response::from_parts(parts, Either::Right(compressed)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{negotiate_encoding, CacheEntry, CompressStage, CompressedCache, Encoding};
+    use backend::engine::Engine;
+    use parts::Rendered;
+    use renderer::{Renderer, Stage};
+
+    use brotli::Decompressor;
+    use bytes::Bytes;
+    use finchers::error;
+    use finchers::prelude::*;
+    use finchers::test;
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use http::header::{self, HeaderValue};
+    use http::{Request, Response};
+    use std::collections::VecDeque;
+    use std::io::Read;
+
+    #[derive(Debug)]
+    struct RepeatEngine;
+    impl Engine<usize> for RepeatEngine {
+        type Body = String;
+        type Error = error::Never;
+        fn render(&self, n: usize) -> Result<Self::Body, Self::Error> {
+            Ok("<p>Hello</p>\n".repeat(n))
+        }
+    }
+
+    fn perform(path: &str, accept_encoding: &str) -> Response<test::TestResult> {
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and(endpoint::syntax::param::<usize>())
+                .and(endpoint::syntax::eos())
+                .wrap(Renderer::new(RepeatEngine).compress().min_size(64))
+        });
+        runner
+            .perform(Request::get(path).header("accept-encoding", accept_encoding))
+            .unwrap()
+    }
+
+    fn decode(response: &Response<test::TestResult>) -> String {
+        let body = response.body().to_bytes().unwrap();
+        let mut decoded = String::new();
+        match response
+            .headers()
+            .get("content-encoding")
+            .map(|h| h.to_str().unwrap())
+        {
+            Some("br") => Decompressor::new(&body[..], 4096)
+                .read_to_string(&mut decoded)
+                .unwrap(),
+            Some("gzip") => GzDecoder::new(&body[..])
+                .read_to_string(&mut decoded)
+                .unwrap(),
+            Some("deflate") => ZlibDecoder::new(&body[..])
+                .read_to_string(&mut decoded)
+                .unwrap(),
+            _ => return String::from_utf8(body.to_vec()).unwrap(),
+        };
+        decoded
+    }
+
+    #[test]
+    fn test_negotiate_encoding() {
+        assert_eq!(
+            negotiate_encoding("gzip, deflate, br"),
+            Some(Encoding::Brotli)
+        );
+        assert_eq!(negotiate_encoding("gzip, deflate"), Some(Encoding::Gzip));
+        assert_eq!(
+            negotiate_encoding("br;q=0.5, deflate"),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(negotiate_encoding("*;q=0.1, br;q=0"), Some(Encoding::Gzip));
+        assert_eq!(negotiate_encoding("identity"), None);
+        assert_eq!(negotiate_encoding("gzip;q=0"), None);
+    }
+
+    #[test]
+    fn test_compress() {
+        for &(accept_encoding, expected) in
+            &[("br", "br"), ("gzip", "gzip"), ("deflate", "deflate")]
+        {
+            let response = perform("/100", accept_encoding);
+            assert_eq!(response.status().as_u16(), 200);
+            assert_matches!(
+                response.headers().get("content-encoding"),
+                Some(h) if h == expected
+            );
+            assert_matches!(
+                response.headers().get("vary"),
+                Some(h) if h == "accept-encoding"
+            );
+            assert!(response.body().to_bytes().unwrap().len() < 1300);
+            assert_eq!(decode(&response), "<p>Hello</p>\n".repeat(100));
+        }
+    }
+
+    #[test]
+    fn test_compress_skipped() {
+        // smaller than the threshold
+        let response = perform("/1", "gzip");
+        assert!(response.headers().get("content-encoding").is_none());
+        assert_matches!(
+            response.headers().get("vary"),
+            Some(h) if h == "accept-encoding"
+        );
+        assert_eq!(response.body().to_utf8().unwrap(), "<p>Hello</p>\n");
+
+        // no acceptable encoding
+        let response = perform("/100", "identity");
+        assert!(response.headers().get("content-encoding").is_none());
+        assert_eq!(decode(&response), "<p>Hello</p>\n".repeat(100));
+    }
+
+    #[test]
+    fn test_compress_with_parts_and_etag() {
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .map(|| {
+                    Rendered::new(100)
+                        .header(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"))
+                })
+                .wrap(Renderer::new(RepeatEngine).with_parts().compress().etag())
+        });
+
+        let response = runner
+            .perform(Request::get("/").header("accept-encoding", "gzip"))
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_matches!(response.headers().get("cache-control"), Some(h) if h == "no-cache");
+        assert_matches!(response.headers().get("content-encoding"), Some(h) if h == "gzip");
+        assert_eq!(decode(&response), "<p>Hello</p>\n".repeat(100));
+        let etag = response.headers().get("etag").cloned().unwrap();
+
+        let response = runner
+            .perform(
+                Request::get("/")
+                    .header("accept-encoding", "gzip")
+                    .header("if-none-match", etag.clone()),
+            )
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 304);
+
+        // the entity tag differs between the encodings.
+        let response = runner
+            .perform(
+                Request::get("/")
+                    .header("accept-encoding", "br")
+                    .header("if-none-match", etag.clone()),
+            )
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_matches!(response.headers().get("etag"), Some(h) if *h != etag);
+    }
+
+    #[test]
+    fn test_compress_reuse() {
+        let renderer = Renderer::new(RepeatEngine);
+        let stage = CompressStage::new(());
+        let first = stage
+            .render(&renderer, (Some(Encoding::Gzip), ()), 200)
+            .unwrap()
+            .into_body()
+            .right()
+            .unwrap();
+        let second = stage
+            .render(&renderer, (Some(Encoding::Gzip), ()), 200)
+            .unwrap()
+            .into_body()
+            .right()
+            .unwrap();
+        // the same buffer is shared between the responses.
+        assert_eq!(first.as_ptr(), second.as_ptr());
+        assert_eq!(stage.cache.lock().unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn test_compressed_cache_hash_collision() {
+        let mut cache = CompressedCache {
+            capacity: 2,
+            entries: VecDeque::new(),
+        };
+        cache.insert(CacheEntry {
+            hash: 42,
+            encoding: Encoding::Gzip,
+            body: Bytes::from_static(b"foo"),
+            compressed: Bytes::from_static(b"compressed foo"),
+        });
+        assert_eq!(
+            cache.get(42, b"foo", Encoding::Gzip),
+            Some(Bytes::from_static(b"compressed foo"))
+        );
+        // the body with the same hash value is not mixed up.
+        assert_eq!(cache.get(42, b"bar", Encoding::Gzip), None);
+        assert_eq!(cache.get(42, b"foo", Encoding::Brotli), None);
+    }
+}
@@ -0,0 +1,348 @@
+#![cfg(feature = "serde-context")]
+
+use finchers::endpoint::wrapper::Wrapper;
+use finchers::endpoint::{ApplyContext, ApplyResult, Endpoint};
+use finchers::error;
+
+use std::borrow::Cow;
+use std::fmt;
+
+use either::Either;
+use futures::{Async, Future, Poll};
+use http::Response;
+use serde::Serialize;
+
+use backend::engine::Engine;
+use backend::template_name::TemplateName;
+use renderer::Renderer;
+
+/// A context value passed to the templates of error pages.
+///
+/// The value is serialized as an object with the following fields:
+///
+/// * `status` - the status code of the response, such as `404`.
+/// * `reason` - the canonical reason phrase of the status code, such as `"Not Found"`.
+/// * `message` - the description of the error.
+/// * `causes` - the descriptions of the underlying causes, or `null` if the debug mode is disabled.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorContext {
+    status: u16,
+    reason: Option<&'static str>,
+    message: String,
+    causes: Option<Vec<String>>,
+    #[serde(skip)]
+    template_name: String,
+}
+
+impl ErrorContext {
+    fn new(err: &error::Error, debug: bool) -> ErrorContext {
+        let status = err.status_code();
+        let causes = if debug {
+            let mut causes = vec![];
+            let mut cause = err.cause();
+            while let Some(fail) = cause {
+                causes.push(fail.to_string());
+                cause = fail.cause();
+            }
+            Some(causes)
+        } else {
+            None
+        };
+        ErrorContext {
+            status: status.as_u16(),
+            reason: status.canonical_reason(),
+            message: err.to_string(),
+            causes,
+            template_name: String::new(),
+        }
+    }
+
+    /// Returns the status code of the response.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the description of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the descriptions of the underlying causes, if the debug mode is enabled.
+    pub fn causes(&self) -> Option<&[String]> {
+        self.causes.as_ref().map(|causes| &causes[..])
+    }
+}
+
+impl TemplateName for ErrorContext {
+    fn template_name(&self) -> &str {
+        &self.template_name
+    }
+}
+
+/// A wrapper which renders the errors from the inner endpoint as HTML error pages.
+///
+/// The template is chosen by the status code of the error (e.g. `404.html`), and
+/// the fallback template (`error.html` by default) is used when rendering the former fails.
+/// If both of them fail, the original error is returned as is.
+///
+/// The errors on routing are also converted into error pages, so this wrapper
+/// should be applied to the endpoint which combines all routes.
+///
+/// The engine should choose the template by `TemplateName`, like
+/// `DynamicTeraEngine` and `DynamicHandlebarsEngine`.
+#[derive(Debug)]
+pub struct ErrorPages<Eng> {
+    renderer: Renderer<Eng>,
+    extension: Cow<'static, str>,
+    fallback: Cow<'static, str>,
+    debug: bool,
+}
+
+impl<Eng> ErrorPages<Eng> {
+    /// Create a new `ErrorPages` from the specified engine.
+    pub fn new(engine: Eng) -> ErrorPages<Eng> {
+        ErrorPages {
+            renderer: Renderer::new(engine),
+            extension: "html".into(),
+            fallback: "error.html".into(),
+            debug: false,
+        }
+    }
+
+    /// Sets the extension of the templates chosen by the status code.
+    ///
+    /// The default value is `"html"`.
+    pub fn extension(mut self, extension: impl Into<Cow<'static, str>>) -> ErrorPages<Eng> {
+        self.extension = extension.into();
+        self
+    }
+
+    /// Sets the name of template used when the template for the status code is unavailable.
+    ///
+    /// The default value is `"error.html"`.
+    pub fn fallback(mut self, name: impl Into<Cow<'static, str>>) -> ErrorPages<Eng> {
+        self.fallback = name.into();
+        self
+    }
+
+    /// Sets whether to pass the cause chain of errors to the templates.
+    ///
+    /// This should be enabled only during development. The default value is `false`.
+    pub fn debug(mut self, enabled: bool) -> ErrorPages<Eng> {
+        self.debug = enabled;
+        self
+    }
+
+    fn render_error(&self, err: error::Error) -> error::Result<Response<Eng::Body>>
+    where
+        Eng: Engine<ErrorContext>,
+    {
+        let status = err.status_code();
+        let ctx = ErrorContext::new(&err, self.debug);
+        let candidates = vec![
+            format!("{}.{}", status.as_u16(), self.extension),
+            self.fallback.to_string(),
+        ];
+        for template_name in candidates {
+            let ctx = ErrorContext {
+                template_name,
+                ..ctx.clone()
+            };
+            if let Ok(mut response) = self.renderer.render_response(ctx) {
+                *response.status_mut() = status;
+                err.headers(response.headers_mut());
+                return Ok(response);
+            }
+        }
+        Err(err)
+    }
+}
+
+impl<'a, E, Eng, T> Wrapper<'a, E> for ErrorPages<Eng>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: Engine<ErrorContext> + 'a,
+{
+    type Output = (Either<T, Response<Eng::Body>>,);
+    type Endpoint = ErrorPagesEndpoint<E, Eng>;
+
+    fn wrap(self, endpoint: E) -> Self::Endpoint {
+        ErrorPagesEndpoint {
+            endpoint,
+            pages: self,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ErrorPagesEndpoint<E, Eng> {
+    endpoint: E,
+    pages: ErrorPages<Eng>,
+}
+
+impl<'a, E, Eng, T> Endpoint<'a> for ErrorPagesEndpoint<E, Eng>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: Engine<ErrorContext> + 'a,
+{
+    type Output = (Either<T, Response<Eng::Body>>,);
+    type Future = ErrorPagesFuture<'a, E, Eng>;
+
+    fn apply(&'a self, cx: &mut ApplyContext<'_>) -> ApplyResult<Self::Future> {
+        let state = match self.endpoint.apply(cx) {
+            Ok(future) => Ok(future),
+            Err(err) => Err(Some(err.into())),
+        };
+        Ok(ErrorPagesFuture {
+            state,
+            pages: &self.pages,
+        })
+    }
+}
+
+pub struct ErrorPagesFuture<'a, E: Endpoint<'a>, Eng> {
+    state: Result<E::Future, Option<error::Error>>,
+    pages: &'a ErrorPages<Eng>,
+}
+
+impl<'a, E, Eng> fmt::Debug for ErrorPagesFuture<'a, E, Eng>
+where
+    E: Endpoint<'a>,
+    E::Future: fmt::Debug,
+    Eng: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ErrorPagesFuture")
+            .field("state", &self.state)
+            .field("pages", &self.pages)
+            .finish()
+    }
+}
+
+impl<'a, E, Eng, T> Future for ErrorPagesFuture<'a, E, Eng>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: Engine<ErrorContext> + 'a,
+{
+    type Item = (Either<T, Response<Eng::Body>>,);
+    type Error = error::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let err = match self.state {
+            Ok(ref mut future) => match future.poll() {
+                Ok(Async::Ready((output,))) => return Ok(Async::Ready((Either::Left(output),))),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(err) => err,
+            },
+            Err(ref mut err) => err.take().expect("cannot poll the future twice"),
+        };
+        self.pages
+            .render_error(err)
+            .map(|response| Async::Ready((Either::Right(response),)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorContext, ErrorPages};
+    use backend::engine::Engine;
+    use backend::template_name::TemplateName;
+
+    use failure;
+    use finchers::error;
+    use finchers::prelude::*;
+    use finchers::test;
+    use http::StatusCode;
+
+    #[derive(Debug)]
+    struct DummyEngine;
+    impl Engine<ErrorContext> for DummyEngine {
+        type Body = String;
+        type Error = failure::Error;
+        fn render(&self, ctx: ErrorContext) -> Result<Self::Body, Self::Error> {
+            match ctx.template_name() {
+                "404.html" => Ok(format!("not found: {}", ctx.message())),
+                "error.html" => Ok(format!(
+                    "{} {:?}: {} {:?}",
+                    ctx.status(),
+                    ctx.reason,
+                    ctx.message(),
+                    ctx.causes()
+                )),
+                name => Err(failure::format_err!("missing template: {}", name)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_pages_routing_error() {
+        let mut runner = test::runner({
+            endpoint::syntax::segment("foo")
+                .and(endpoint::syntax::eos())
+                .map(|| "foo")
+                .wrap(ErrorPages::new(DummyEngine))
+        });
+
+        let response = runner.perform("/foo").unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.body().to_utf8().unwrap(), "foo");
+
+        let response = runner.perform("/bar").unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+        assert_matches!(
+            response.headers().get("content-type"),
+            Some(h) if h == "text/html; charset=utf-8"
+        );
+        assert!(response
+            .body()
+            .to_utf8()
+            .unwrap()
+            .starts_with("not found: "));
+    }
+
+    #[test]
+    fn test_error_pages_fallback() {
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and_then(|| {
+                    Err::<&str, _>(error::fail(
+                        failure::err_msg("disk full").context("failed to save"),
+                    ))
+                })
+                .wrap(ErrorPages::new(DummyEngine))
+        });
+        let response = runner.perform("/").unwrap();
+        assert_eq!(response.status().as_u16(), 500);
+        assert_eq!(
+            response.body().to_utf8().unwrap(),
+            "500 Some(\"Internal Server Error\"): failed to save None"
+        );
+
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and_then(|| {
+                    Err::<&str, _>(error::fail(
+                        failure::err_msg("disk full").context("failed to save"),
+                    ))
+                })
+                .wrap(ErrorPages::new(DummyEngine).debug(true))
+        });
+        let response = runner.perform("/").unwrap();
+        assert_eq!(
+            response.body().to_utf8().unwrap(),
+            "500 Some(\"Internal Server Error\"): failed to save Some([\"disk full\"])"
+        );
+    }
+
+    #[test]
+    fn test_error_pages_unavailable() {
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and_then(|| Err::<&str, _>(error::err_msg(StatusCode::BAD_REQUEST, "bad")))
+                .wrap(ErrorPages::new(DummyEngine).fallback("missing.html"))
+        });
+        let response = runner.perform("/").unwrap();
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(response.body().to_utf8().unwrap(), "bad");
+    }
+}
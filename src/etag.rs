@@ -0,0 +1,347 @@
+use finchers::endpoint::{ApplyContext, ApplyResult};
+use finchers::error;
+
+use std::borrow::Cow;
+
+use futures::future::{self, FutureResult, Join};
+use futures::Future;
+use http::header;
+use http::header::HeaderValue;
+use http::{Method, Response, StatusCode};
+
+use parts::Rendered;
+use renderer::{Renderer, Stage};
+
+/// A trait representing a context value which provides a cheap version key.
+///
+/// The stage added by `Renderer::etag_by_version` computes the `ETag`
+/// from this key, so the rendering can be skipped entirely when the client
+/// already has the current version.
+pub trait Versioned {
+    /// Returns the key which changes whenever the rendered output changes.
+    fn version(&self) -> Cow<'_, str>;
+}
+
+impl<T: Versioned + ?Sized> Versioned for &T {
+    fn version(&self) -> Cow<'_, str> {
+        (**self).version()
+    }
+}
+
+impl<T: Versioned + ?Sized> Versioned for Box<T> {
+    fn version(&self) -> Cow<'_, str> {
+        (**self).version()
+    }
+}
+
+impl<T: Versioned> Versioned for Rendered<T> {
+    fn version(&self) -> Cow<'_, str> {
+        self.context().version()
+    }
+}
+
+/// A trait representing the way to compute the entity tag of the rendered responses.
+///
+/// This trait is implemented by `HashBody` and `ByVersion`.
+pub trait TagSource<T, B> {
+    #[doc(hidden)]
+    fn context_tag(&self, _ctx: &T) -> Option<HeaderValue> {
+        None
+    }
+
+    #[doc(hidden)]
+    fn body_tag(&self, _body: &B) -> Option<HeaderValue> {
+        None
+    }
+}
+
+/// Computes the entity tag from the hash of the rendered body.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HashBody(());
+
+impl<T, B: AsRef<[u8]>> TagSource<T, B> for HashBody {
+    fn body_tag(&self, body: &B) -> Option<HeaderValue> {
+        Some(entity_tag(body.as_ref()))
+    }
+}
+
+/// Computes the entity tag from the version key of the context value.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByVersion(());
+
+impl<T: Versioned, B> TagSource<T, B> for ByVersion {
+    fn context_tag(&self, ctx: &T) -> Option<HeaderValue> {
+        Some(entity_tag(ctx.version().as_bytes()))
+    }
+}
+
+fn entity_tag(data: &[u8]) -> HeaderValue {
+    // The 64-bit FNV-1a hash is used since its value is specified, so the tags
+    // stay the same across the builds and the servers sharing the caches.
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let hash = data.iter().fold(OFFSET_BASIS, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(PRIME)
+    });
+    HeaderValue::from_str(&format!("\"{:016x}\"", hash)).expect("should be a valid header value")
+}
+
+/// Returns whether the value of `If-None-Match` matches the specified entity tag,
+/// using the weak comparison.
+fn matches_tag(if_none_match: &str, etag: &HeaderValue) -> bool {
+    let etag = match etag.to_str() {
+        Ok(etag) => etag.trim_start_matches("W/"),
+        Err(..) => return false,
+    };
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+impl<Eng, S> Renderer<Eng, S> {
+    /// Adds a stage which sets a strong `ETag` computed from the rendered body.
+    ///
+    /// When the value of `If-None-Match` in a `GET` or `HEAD` request matches
+    /// the tag, an empty `304 Not Modified` response is returned instead.
+    /// The responses with a status code other than `2xx` are left untagged.
+    pub fn etag(self) -> Renderer<Eng, ETagStage<S, HashBody>> {
+        self.with_stage(|stage| ETagStage {
+            stage,
+            source: HashBody(()),
+        })
+    }
+
+    /// Adds a stage which sets the `ETag` computed from the version key of the context.
+    ///
+    /// Unlike `etag`, the rendering is skipped when the client has the current version.
+    pub fn etag_by_version(self) -> Renderer<Eng, ETagStage<S, ByVersion>> {
+        self.with_stage(|stage| ETagStage {
+            stage,
+            source: ByVersion(()),
+        })
+    }
+}
+
+/// A stage which supports the conditional requests using `ETag`.
+///
+/// The value of this type is added by `Renderer::etag` or `Renderer::etag_by_version`.
+#[derive(Debug)]
+pub struct ETagStage<S, Src> {
+    stage: S,
+    source: Src,
+}
+
+impl<'a, Eng, S, Src, T> Stage<'a, Eng, T> for ETagStage<S, Src>
+where
+    S: Stage<'a, Eng, T>,
+    Src: TagSource<T, S::Body>,
+{
+    type Body = Option<S::Body>;
+    type State = (Option<String>, S::State);
+    type Prepare = Join<FutureResult<Option<String>, error::Error>, S::Prepare>;
+
+    fn prepare(&'a self, cx: &mut ApplyContext<'_>) -> ApplyResult<Self::Prepare> {
+        let if_none_match = match *cx.input().method() {
+            Method::GET | Method::HEAD => cx
+                .input()
+                .headers()
+                .get(header::IF_NONE_MATCH)
+                .and_then(|h| h.to_str().ok())
+                .map(ToOwned::to_owned),
+            _ => None,
+        };
+        let state = self.stage.prepare(cx)?;
+        Ok(future::ok(if_none_match).join(state))
+    }
+
+    fn render<R>(
+        &self,
+        renderer: &Renderer<Eng, R>,
+        (if_none_match, state): Self::State,
+        value: T,
+    ) -> error::Result<Response<Self::Body>> {
+        let not_modified = |etag: &HeaderValue| match if_none_match {
+            Some(ref if_none_match) => matches_tag(if_none_match, etag),
+            None => false,
+        };
+
+        let etag = self.source.context_tag(&value);
+        if let Some(ref etag) = etag {
+            if not_modified(etag) {
+                return Ok(not_modified_response(etag.clone()));
+            }
+        }
+
+        let response = self.stage.render(renderer, state, value)?;
+        if !response.status().is_success() {
+            return Ok(response.map(Some));
+        }
+        let etag = match etag.or_else(|| self.source.body_tag(response.body())) {
+            Some(etag) => etag,
+            None => return Ok(response.map(Some)),
+        };
+        if not_modified(&etag) {
+            return Ok(not_modified_response(etag));
+        }
+        let mut response = response.map(Some);
+        response.headers_mut().insert(header::ETAG, etag);
+        Ok(response)
+    }
+}
+
+fn not_modified_response<B>(etag: HeaderValue) -> Response<Option<B>> {
+    let mut response = Response::new(None);
+    *response.status_mut() = StatusCode::NOT_MODIFIED;
+    response.headers_mut().insert(header::ETAG, etag);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entity_tag, matches_tag, Versioned};
+    use backend::engine::Engine;
+    use parts::Rendered;
+    use renderer::Renderer;
+
+    use finchers::error;
+    use finchers::prelude::*;
+    use finchers::test;
+    use http::header::{self, HeaderValue};
+    use http::{Request, StatusCode};
+    use std::borrow::Cow;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(Debug)]
+    struct Page {
+        version: u32,
+        body: String,
+    }
+
+    impl Versioned for Page {
+        fn version(&self) -> Cow<'_, str> {
+            self.version.to_string().into()
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct CountingEngine {
+        count: Rc<Cell<usize>>,
+    }
+    impl Engine<Page> for CountingEngine {
+        type Body = String;
+        type Error = error::Never;
+        fn render(&self, page: Page) -> Result<Self::Body, Self::Error> {
+            self.count.set(self.count.get() + 1);
+            Ok(page.body)
+        }
+    }
+
+    fn page() -> Page {
+        Page {
+            version: 1,
+            body: "Hello".into(),
+        }
+    }
+
+    #[test]
+    fn test_entity_tag() {
+        assert_eq!(entity_tag(b""), "\"cbf29ce484222325\"");
+        assert_eq!(entity_tag(b"a"), "\"af63dc4c8601ec8c\"");
+    }
+
+    #[test]
+    fn test_matches_tag() {
+        let etag = HeaderValue::from_static("\"abc\"");
+        assert!(matches_tag("\"abc\"", &etag));
+        assert!(matches_tag("W/\"abc\"", &etag));
+        assert!(matches_tag("\"xyz\", \"abc\"", &etag));
+        assert!(matches_tag("*", &etag));
+        assert!(!matches_tag("\"xyz\"", &etag));
+    }
+
+    #[test]
+    fn test_etag() {
+        let engine = CountingEngine::default();
+        let count = engine.count.clone();
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .map(page)
+                .wrap(Renderer::new(engine).etag())
+        });
+
+        let response = runner.perform("/").unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.body().to_utf8().unwrap(), "Hello");
+        let etag = response.headers().get("etag").cloned().unwrap();
+
+        let response = runner
+            .perform(Request::get("/").header("if-none-match", etag.clone()))
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 304);
+        assert_matches!(response.headers().get("etag"), Some(h) if *h == etag);
+        assert_eq!(response.body().to_utf8().unwrap(), "");
+        assert_eq!(count.get(), 2);
+
+        let response = runner
+            .perform(Request::get("/").header("if-none-match", "\"stale\""))
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.body().to_utf8().unwrap(), "Hello");
+    }
+
+    #[test]
+    fn test_etag_by_version() {
+        let engine = CountingEngine::default();
+        let count = engine.count.clone();
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .map(page)
+                .wrap(Renderer::new(engine).etag_by_version())
+        });
+
+        let response = runner.perform("/").unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let etag = response.headers().get("etag").cloned().unwrap();
+        assert_eq!(count.get(), 1);
+
+        let response = runner
+            .perform(Request::get("/").header("if-none-match", etag))
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 304);
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn test_etag_with_parts() {
+        let engine = CountingEngine::default();
+        let count = engine.count.clone();
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and(endpoint::syntax::param::<u16>())
+                .map(|status: u16| {
+                    Rendered::new(page())
+                        .status(StatusCode::from_u16(status).unwrap())
+                        .header(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"))
+                })
+                .wrap(Renderer::new(engine).with_parts().etag_by_version())
+        });
+
+        let response = runner.perform("/200").unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_matches!(response.headers().get("cache-control"), Some(h) if h == "no-cache");
+        let etag = response.headers().get("etag").cloned().unwrap();
+
+        let response = runner
+            .perform(Request::get("/200").header("if-none-match", etag))
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 304);
+        assert_eq!(count.get(), 1);
+
+        // the error responses are not tagged.
+        let response = runner.perform("/404").unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+        assert_eq!(response.headers().get("etag"), None);
+    }
+}
@@ -0,0 +1,338 @@
+#![cfg(feature = "serde-context")]
+
+use finchers::endpoint;
+use finchers::endpoint::{ApplyContext, ApplyResult, Endpoint};
+use finchers::error;
+
+use std::borrow::Cow;
+
+use failure;
+use futures::future::Join;
+use futures::Future;
+use http::Response;
+use serde::ser::{Error, Serialize, Serializer};
+use serde_json::{self, Map, Value};
+
+use backend::template_name::TemplateName;
+use renderer::{Renderer, Stage};
+
+/// A set of values merged into every context value under a reserved key.
+///
+/// The values consist of the global ones registered by `value`, and the
+/// per-request ones extracted by the endpoint specified by `request`.
+/// The latter take precedence when both of them have the same name.
+///
+/// The template can refer to these values through the reserved key,
+/// such as `{{ globals.version }}`.
+#[derive(Debug)]
+pub struct Globals<G = endpoint::Cloned<()>> {
+    key: Cow<'static, str>,
+    values: Map<String, Value>,
+    endpoint: G,
+}
+
+impl Globals {
+    /// Create an empty `Globals` stored under the key `"globals"`.
+    pub fn new() -> Globals {
+        Globals {
+            key: "globals".into(),
+            values: Map::new(),
+            endpoint: endpoint::cloned(()),
+        }
+    }
+}
+
+impl Default for Globals {
+    fn default() -> Globals {
+        Globals::new()
+    }
+}
+
+impl<G> Globals<G> {
+    /// Sets the name of key under which the values are merged into the context.
+    ///
+    /// The default value is `"globals"`.
+    pub fn key(mut self, key: impl Into<Cow<'static, str>>) -> Globals<G> {
+        self.key = key.into();
+        self
+    }
+
+    /// Registers a global value shared by all requests.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the value cannot be serialized to JSON.
+    pub fn value(mut self, name: impl Into<String>, value: impl Serialize) -> Globals<G> {
+        let value = serde_json::to_value(value).expect("should be serializable to JSON");
+        self.values.insert(name.into(), value);
+        self
+    }
+
+    /// Sets the endpoint which extracts the per-request values.
+    ///
+    /// The output of this endpoint must be serialized as a map (or `null`).
+    ///
+    /// The endpoint is applied before the wrapped endpoint, and must not consume
+    /// the path segments (e.g. it should read the headers or the whole URI) so
+    /// that the wrapped endpoint can match the path.
+    pub fn request<G2>(self, endpoint: G2) -> Globals<G2> {
+        Globals {
+            key: self.key,
+            values: self.values,
+            endpoint,
+        }
+    }
+
+    fn merge<R: Serialize>(&self, request: R) -> error::Result<Map<String, Value>> {
+        let mut values = self.values.clone();
+        match serde_json::to_value(request)
+            .map_err(|err| error::Error::from(failure::Error::from(err)))?
+        {
+            Value::Null => {}
+            Value::Object(request) => values.extend(request),
+            _ => {
+                return Err(error::Error::from(failure::err_msg(
+                    "the per-request globals must be serialized as a map",
+                )));
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// A context value combined with the values provided by `Globals`.
+///
+/// The value is serialized as the inner context with an additional
+/// entry containing the globals, so the inner context must be serialized
+/// as a map which does not use the reserved key.
+#[derive(Debug, Clone)]
+pub struct WithGlobals<CtxT> {
+    ctx: CtxT,
+    key: Cow<'static, str>,
+    globals: Map<String, Value>,
+}
+
+impl<CtxT> WithGlobals<CtxT> {
+    /// Returns a reference to the inner context value.
+    pub fn context(&self) -> &CtxT {
+        &self.ctx
+    }
+
+    /// Returns the value of global with the specified name.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.globals.get(name)
+    }
+}
+
+impl<CtxT: TemplateName> TemplateName for WithGlobals<CtxT> {
+    fn template_name(&self) -> &str {
+        self.ctx.template_name()
+    }
+}
+
+impl<CtxT: Serialize> Serialize for WithGlobals<CtxT> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = match serde_json::to_value(&self.ctx).map_err(S::Error::custom)? {
+            Value::Object(map) => map,
+            _ => {
+                return Err(S::Error::custom(
+                    "the context value must be serialized as a map",
+                ))
+            }
+        };
+        if map.contains_key(&*self.key) {
+            return Err(S::Error::custom(format!(
+                "the key `{}` is reserved for the globals",
+                self.key
+            )));
+        }
+        map.insert(self.key.to_string(), Value::Object(self.globals.clone()));
+        map.serialize(serializer)
+    }
+}
+
+impl<Eng, S> Renderer<Eng, S> {
+    /// Adds a stage which merges the specified globals into the context values.
+    pub fn with_globals<G>(self, globals: Globals<G>) -> Renderer<Eng, GlobalsStage<S, G>> {
+        self.with_stage(|stage| GlobalsStage { stage, globals })
+    }
+}
+
+/// A stage which merges the values of `Globals` into the context values.
+///
+/// The value of this type is added by `Renderer::with_globals`.
+#[derive(Debug)]
+pub struct GlobalsStage<S, G> {
+    stage: S,
+    globals: Globals<G>,
+}
+
+impl<'a, Eng, S, G, T, Req> Stage<'a, Eng, T> for GlobalsStage<S, G>
+where
+    S: Stage<'a, Eng, WithGlobals<T>>,
+    G: Endpoint<'a, Output = (Req,)> + 'a,
+    Req: Serialize,
+{
+    type Body = S::Body;
+    type State = ((Req,), S::State);
+    type Prepare = Join<G::Future, S::Prepare>;
+
+    fn prepare(&'a self, cx: &mut ApplyContext<'_>) -> ApplyResult<Self::Prepare> {
+        let request = self.globals.endpoint.apply(cx)?;
+        let state = self.stage.prepare(cx)?;
+        Ok(request.join(state))
+    }
+
+    fn render<R>(
+        &self,
+        renderer: &Renderer<Eng, R>,
+        ((request,), state): Self::State,
+        ctx: T,
+    ) -> error::Result<Response<Self::Body>> {
+        let globals = self.globals.merge(request)?;
+        let ctx = WithGlobals {
+            ctx,
+            key: self.globals.key.clone(),
+            globals,
+        };
+        self.stage.render(renderer, state, ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Globals, WithGlobals};
+    use backend::engine::Engine;
+    use parts::Rendered;
+    use renderer::Renderer;
+
+    use failure;
+    use finchers::error;
+    use finchers::prelude::*;
+    use finchers::test;
+    use http::StatusCode;
+    use serde::Serialize;
+    use serde_json;
+
+    #[derive(Debug, Serialize)]
+    struct Context {
+        name: String,
+    }
+
+    #[derive(Debug, Serialize)]
+    struct RequestInfo {
+        path: String,
+    }
+
+    #[derive(Debug)]
+    struct JsonEngine;
+    impl<T: Serialize> Engine<T> for JsonEngine {
+        type Body = String;
+        type Error = failure::Error;
+        fn render(&self, value: T) -> Result<Self::Body, Self::Error> {
+            Ok(serde_json::to_string(&value)?)
+        }
+    }
+
+    #[test]
+    fn test_globals() {
+        let globals = Globals::new()
+            .value("version", "1.0.0")
+            .value("path", "overridden")
+            .request(endpoint::apply(|cx| {
+                Ok(Ok::<_, error::Error>(RequestInfo {
+                    path: cx.input().uri().path().to_owned(),
+                }))
+            }));
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and(endpoint::syntax::param::<String>())
+                .and(endpoint::syntax::eos())
+                .map(|name| Context { name })
+                .wrap(Renderer::new(JsonEngine).with_globals(globals))
+        });
+
+        let response = runner.perform("/Alice").unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(
+            response.body().to_utf8().unwrap(),
+            r#"{"globals":{"path":"/Alice","version":"1.0.0"},"name":"Alice"}"#
+        );
+    }
+
+    #[test]
+    fn test_globals_applied_before_route() {
+        let globals = Globals::new().request(endpoint::apply(|cx| {
+            Ok(Ok::<_, error::Error>(RequestInfo {
+                path: cx.remaining_path().percent_decode_lossy().into_owned(),
+            }))
+        }));
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and(endpoint::syntax::param::<String>())
+                .and(endpoint::syntax::eos())
+                .map(|name| Context { name })
+                .wrap(Renderer::new(JsonEngine).with_globals(globals))
+        });
+
+        // the globals endpoint sees the path before the route consumes it.
+        let response = runner.perform("/Alice").unwrap();
+        assert_eq!(
+            response.body().to_utf8().unwrap(),
+            r#"{"globals":{"path":"Alice"},"name":"Alice"}"#
+        );
+    }
+
+    #[test]
+    fn test_globals_with_parts() {
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .map(|| {
+                    Rendered::new(Context {
+                        name: "Alice".into(),
+                    })
+                    .status(StatusCode::NOT_FOUND)
+                })
+                .wrap(
+                    Renderer::new(JsonEngine)
+                        .with_globals(Globals::new().value("version", "1.0.0"))
+                        .with_parts(),
+                )
+        });
+
+        let response = runner.perform("/").unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+        assert_eq!(
+            response.body().to_utf8().unwrap(),
+            r#"{"globals":{"version":"1.0.0"},"name":"Alice"}"#
+        );
+    }
+
+    #[test]
+    fn test_globals_reserved_key() {
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .map(|| Context {
+                    name: "Alice".into(),
+                })
+                .wrap(Renderer::new(JsonEngine).with_globals(Globals::new().key("name")))
+        });
+        let response = runner.perform("/").unwrap();
+        assert_eq!(response.status().as_u16(), 500);
+    }
+
+    #[test]
+    fn test_with_globals_get() {
+        let globals = Globals::new().value("version", 1);
+        let ctx = WithGlobals {
+            ctx: (),
+            key: globals.key.clone(),
+            globals: globals.merge(()).unwrap(),
+        };
+        assert_eq!(ctx.get("version"), Some(&serde_json::Value::from(1)));
+        assert_eq!(ctx.get("path"), None);
+    }
+}
@@ -15,29 +15,33 @@
 #![cfg_attr(finchers_deny_warnings, deny(warnings))]
 #![cfg_attr(finchers_deny_warnings, doc(test(attr(deny(warnings)))))]
 
+extern crate bytes;
+extern crate either;
 extern crate failure;
 extern crate finchers;
 #[macro_use]
 extern crate futures;
 extern crate http;
+extern crate hyper;
 #[macro_use]
 extern crate lazy_static;
 extern crate mime;
-#[cfg(
-    any(
-        feature = "use-tera",
-        feature = "use-handlebars",
-        feature = "use-askama"
-    )
-)]
+#[cfg(feature = "mime_guess")]
 extern crate mime_guess;
-
-#[cfg(any(feature = "use-tera", feature = "use-handlebars"))]
-#[cfg_attr(
-    all(test, any(feature = "use-handlebars", feature = "use-tera")),
-    macro_use
-)]
 extern crate serde;
+extern crate serde_json;
+
+#[cfg(feature = "compression")]
+extern crate brotli;
+#[cfg(feature = "compression")]
+extern crate flate2;
+
+#[cfg(feature = "use-cbor")]
+extern crate ciborium;
+#[cfg(feature = "use-msgpack")]
+extern crate rmp_serde;
+#[cfg(feature = "use-yaml")]
+extern crate serde_yaml;
 
 #[cfg(test)]
 #[macro_use]
@@ -46,6 +50,9 @@ extern crate matches;
 #[cfg(feature = "use-handlebars")]
 extern crate handlebars;
 
+#[cfg(feature = "use-sailfish")]
+extern crate sailfish;
+
 #[cfg(feature = "use-tera")]
 extern crate tera;
 
@@ -56,19 +63,84 @@ extern crate askama;
 #[cfg_attr(test, macro_use)]
 extern crate horrorshow;
 
+#[cfg(feature = "use-liquid")]
+extern crate liquid;
+
+#[cfg(feature = "use-maud")]
+extern crate maud;
+
+#[cfg(feature = "use-minijinja")]
+extern crate minijinja;
+
+#[cfg(feature = "use-mustache")]
+extern crate mustache;
+
 pub mod backend;
+mod blocking;
+mod compress;
+mod error_page;
+mod etag;
+mod globals;
+mod minify;
+mod negotiate;
+mod parts;
+mod post_process;
 mod renderer;
+mod stream;
 
-pub use self::renderer::Renderer;
+pub use self::blocking::{BlockingRenderer, RenderPool};
+#[cfg(feature = "compression")]
+pub use self::compress::CompressStage;
+#[cfg(feature = "serde-context")]
+pub use self::error_page::{ErrorContext, ErrorPages};
+pub use self::etag::{ByVersion, ETagStage, HashBody, TagSource, Versioned};
+#[cfg(feature = "serde-context")]
+pub use self::globals::{Globals, GlobalsStage, WithGlobals};
+pub use self::minify::HtmlMinifier;
+pub use self::negotiate::NegotiatingRenderer;
+pub use self::parts::{PartsStage, Rendered};
+pub use self::post_process::{PostProcess, PostProcessStage};
+pub use self::renderer::{Renderer, Stage};
+pub use self::stream::StreamingRenderer;
 
 #[cfg(feature = "use-askama")]
 pub use self::backend::askama::askama;
 
+#[cfg(feature = "use-cbor")]
+pub use self::backend::cbor::cbor;
+
 #[cfg(feature = "use-handlebars")]
-pub use self::backend::handlebars::handlebars;
+pub use self::backend::handlebars::{handlebars, handlebars_dynamic};
 
 #[cfg(feature = "use-horrorshow")]
 pub use self::backend::horrorshow::horrorshow;
 
+#[cfg(feature = "use-json")]
+pub use self::backend::json::{json, json_pretty};
+
+#[cfg(feature = "use-liquid")]
+pub use self::backend::liquid::{liquid, liquid_dynamic};
+
+#[cfg(feature = "use-maud")]
+pub use self::backend::maud::maud;
+
+#[cfg(feature = "use-minijinja")]
+pub use self::backend::minijinja::{minijinja, minijinja_dynamic};
+
+#[cfg(feature = "use-msgpack")]
+pub use self::backend::msgpack::msgpack;
+
+#[cfg(feature = "use-mustache")]
+pub use self::backend::mustache::{mustache, mustache_dynamic};
+
+#[cfg(feature = "use-ructe")]
+pub use self::backend::ructe::ructe;
+
+#[cfg(feature = "use-sailfish")]
+pub use self::backend::sailfish::{sailfish, sailfish_with_extension};
+
 #[cfg(feature = "use-tera")]
-pub use self::backend::tera::tera;
+pub use self::backend::tera::{tera, tera_dynamic};
+
+#[cfg(feature = "use-yaml")]
+pub use self::backend::yaml::yaml;
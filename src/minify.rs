@@ -0,0 +1,227 @@
+use http::header::HeaderValue;
+use mime;
+use mime::Mime;
+
+use post_process::PostProcess;
+
+/// The elements whose contents are preserved as is.
+const RAW_ELEMENTS: [&str; 4] = ["pre", "textarea", "script", "style"];
+
+/// A post-render hook which minifies the HTML documents.
+///
+/// The minifier collapses the runs of whitespace into a single space and strips
+/// the comments (except the conditional comments such as `<!--[if IE]>`), while
+/// the contents of `<pre>`, `<textarea>`, `<script>` and `<style>` are kept intact.
+/// The bodies are left unchanged unless the content-type is `text/html`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HtmlMinifier {
+    _priv: (),
+}
+
+impl HtmlMinifier {
+    /// Create a new `HtmlMinifier`.
+    pub fn new() -> HtmlMinifier {
+        HtmlMinifier { _priv: () }
+    }
+
+    /// Minifies the specified HTML document.
+    pub fn minify(&self, html: &str) -> String {
+        let mut out = String::with_capacity(html.len());
+        let mut rest = html;
+        let mut pending_space = false;
+        while let Some(ch) = rest.chars().next() {
+            if rest.starts_with("<!--") {
+                let end = rest.find("-->").map_or(rest.len(), |pos| pos + 3);
+                if rest.starts_with("<!--[if") {
+                    flush_space(&mut out, &mut pending_space);
+                    out.push_str(&rest[..end]);
+                }
+                rest = &rest[end..];
+                continue;
+            }
+
+            if ch == '<' && is_tag_start(&rest[1..]) {
+                flush_space(&mut out, &mut pending_space);
+                let end = copy_tag(&mut out, rest);
+                let name = tag_name(&rest[1..end]);
+                rest = &rest[end..];
+                if let Some(name) = name.filter(|name| RAW_ELEMENTS.contains(&&name[..])) {
+                    let end = find_closing_tag(rest, &name);
+                    out.push_str(&rest[..end]);
+                    rest = &rest[end..];
+                }
+                continue;
+            }
+
+            if ch.is_ascii_whitespace() {
+                pending_space = !out.is_empty();
+            } else {
+                flush_space(&mut out, &mut pending_space);
+                out.push(ch);
+            }
+            rest = &rest[ch.len_utf8()..];
+        }
+        out
+    }
+}
+
+impl PostProcess<String> for HtmlMinifier {
+    fn post_process(&self, content_type: &HeaderValue, body: String) -> String {
+        if is_html(content_type) {
+            self.minify(&body)
+        } else {
+            body
+        }
+    }
+}
+
+fn is_html(content_type: &HeaderValue) -> bool {
+    match content_type
+        .to_str()
+        .ok()
+        .and_then(|s| s.parse::<Mime>().ok())
+    {
+        Some(mime) => mime.type_() == mime::TEXT && mime.subtype() == mime::HTML,
+        None => false,
+    }
+}
+
+fn flush_space(out: &mut String, pending_space: &mut bool) {
+    if *pending_space {
+        out.push(' ');
+        *pending_space = false;
+    }
+}
+
+fn is_tag_start(s: &str) -> bool {
+    match s.chars().next() {
+        Some(ch) => ch.is_ascii_alphabetic() || ch == '/' || ch == '!',
+        None => false,
+    }
+}
+
+/// Copies a tag at the beginning of `s` while collapsing the whitespace
+/// outside of attribute values, and returns the length of the tag.
+fn copy_tag(out: &mut String, s: &str) -> usize {
+    let mut quote = None;
+    let mut pending_space = false;
+    for (i, ch) in s.char_indices() {
+        match quote {
+            Some(q) => {
+                out.push(ch);
+                if ch == q {
+                    quote = None;
+                }
+            }
+            None if ch.is_ascii_whitespace() => pending_space = true,
+            None => {
+                if pending_space && ch != '>' {
+                    out.push(' ');
+                }
+                pending_space = false;
+                out.push(ch);
+                match ch {
+                    '"' | '\'' => quote = Some(ch),
+                    '>' => return i + 1,
+                    _ => {}
+                }
+            }
+        }
+    }
+    s.len()
+}
+
+/// Returns the lowercased name of an opening tag.
+fn tag_name(tag: &str) -> Option<String> {
+    let name: String = tag
+        .chars()
+        .take_while(|ch| ch.is_ascii_alphanumeric())
+        .collect();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_ascii_lowercase())
+    }
+}
+
+/// Returns the position of the closing tag of the raw element `name`.
+fn find_closing_tag(s: &str, name: &str) -> usize {
+    let closing = format!("</{}", name);
+    let lowercased = s.to_ascii_lowercase();
+    let mut start = 0;
+    while let Some(pos) = lowercased[start..].find(&closing) {
+        let pos = start + pos;
+        // The name must not be followed by other characters (e.g. `</preview>`).
+        match lowercased.as_bytes().get(pos + closing.len()) {
+            Some(&b) if b == b'>' || b == b'/' || b.is_ascii_whitespace() => return pos,
+            None => return pos,
+            Some(..) => start = pos + closing.len(),
+        }
+    }
+    s.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HtmlMinifier;
+
+    #[test]
+    fn test_minify() {
+        let html = r#"
+<!DOCTYPE html>
+<html>
+  <!-- a comment -->
+  <head>
+    <title>  Hello,   world  </title>
+    <!--[if IE]><p>IE</p><![endif]-->
+  </head>
+  <body   class="a  b"
+        id='main'>
+    <p>
+      Hello
+    </p>
+  </body>
+</html>
+"#;
+        assert_eq!(
+            HtmlMinifier::new().minify(html),
+            "<!DOCTYPE html> <html> <head> <title> Hello, world </title> \
+             <!--[if IE]><p>IE</p><![endif]--> </head> \
+             <body class=\"a  b\" id='main'> <p> Hello </p> </body> </html>"
+        );
+    }
+
+    #[test]
+    fn test_minify_raw_elements() {
+        let html = "<div>\n  <PRE>\n  a\n    b\n</PRE>\n  <textarea>  x  </textarea>\n  \
+                    <script>if (a < b) {\n  f();\n}</script>\n  \
+                    <style>p  {  color: red;  }</style>\n</div>";
+        assert_eq!(
+            HtmlMinifier::new().minify(html),
+            "<div> <PRE>\n  a\n    b\n</PRE> <textarea>  x  </textarea> \
+             <script>if (a < b) {\n  f();\n}</script> \
+             <style>p  {  color: red;  }</style> </div>"
+        );
+    }
+
+    #[test]
+    fn test_minify_text_with_less_than() {
+        assert_eq!(HtmlMinifier::new().minify("<p>1  < 2</p>"), "<p>1 < 2</p>");
+    }
+
+    #[test]
+    fn test_minify_non_ascii_whitespace() {
+        assert_eq!(
+            HtmlMinifier::new().minify("<p>a\u{a0}\u{a0}b \u{2003} c</p>"),
+            "<p>a\u{a0}\u{a0}b \u{2003} c</p>"
+        );
+    }
+
+    #[test]
+    fn test_minify_similar_closing_tag() {
+        assert_eq!(
+            HtmlMinifier::new().minify("<pre>a  </preview>  b</pre >  <p>c</p>"),
+            "<pre>a  </preview>  b</pre> <p>c</p>"
+        );
+    }
+}
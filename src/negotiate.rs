@@ -0,0 +1,325 @@
+use finchers::endpoint::wrapper::Wrapper;
+use finchers::endpoint::{ApplyContext, ApplyResult, Endpoint};
+use finchers::error;
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use either::Either;
+use failure;
+use futures::{Async, Future, Poll};
+use http::header;
+use http::header::HeaderValue;
+use http::{Response, StatusCode};
+use mime;
+use mime::Mime;
+use serde::Serialize;
+use serde_json;
+
+use backend::engine::Engine;
+use renderer::Renderer;
+
+lazy_static! {
+    static ref JSON_CONTENT_TYPE: HeaderValue = HeaderValue::from_static("application/json");
+}
+
+impl<Eng> Renderer<Eng> {
+    /// Converts this renderer into one which chooses the representation by the `Accept` header.
+    ///
+    /// The context value is rendered by the template engine when the content-type
+    /// of the template is preferred, and serialized to JSON when `application/json` is
+    /// preferred. If neither of them is acceptable, the request fails with
+    /// `406 Not Acceptable`. The responses include `Vary: Accept` in any case.
+    pub fn negotiate(self) -> NegotiatingRenderer<Eng> {
+        NegotiatingRenderer { renderer: self }
+    }
+}
+
+/// A renderer which switches between the template and JSON based on the `Accept` header.
+///
+/// The value of this type is created by `Renderer::negotiate`.
+#[derive(Debug)]
+pub struct NegotiatingRenderer<Eng> {
+    renderer: Renderer<Eng>,
+}
+
+impl<Eng> NegotiatingRenderer<Eng> {
+    fn render_response<T>(
+        &self,
+        value: T,
+        accept: Option<&[MediaRange]>,
+    ) -> error::Result<Response<Either<Eng::Body, String>>>
+    where
+        Eng: Engine<T>,
+        T: Serialize,
+    {
+        let content_type = self.renderer.get_content_type(&value);
+        // An `Accept` header without any media range is treated as `*/*`.
+        let accept = match accept {
+            Some(accept) if !accept.is_empty() => accept,
+            _ => return self.render_template(value),
+        };
+
+        let template_mime = content_type
+            .to_str()
+            .ok()
+            .and_then(|s| s.parse::<Mime>().ok())
+            .unwrap_or(mime::TEXT_HTML);
+        let template_q = quality(accept, &template_mime);
+        let json_q = quality(accept, &mime::APPLICATION_JSON);
+        match (template_q, json_q) {
+            (0, 0) => Err(error::err_msg(
+                StatusCode::NOT_ACCEPTABLE,
+                "none of the available representations is acceptable",
+            )),
+            (t, j) if t >= j => self.render_template(value),
+            _ => {
+                let body = serde_json::to_string(&value)
+                    .map_err(|err| error::Error::from(failure::Error::from(err)))?;
+                let mut response = Response::new(Either::Right(body));
+                response
+                    .headers_mut()
+                    .insert(header::CONTENT_TYPE, JSON_CONTENT_TYPE.clone());
+                Ok(response)
+            }
+        }
+    }
+
+    fn render_template<T>(&self, value: T) -> error::Result<Response<Either<Eng::Body, String>>>
+    where
+        Eng: Engine<T>,
+    {
+        self.renderer
+            .render_response(value)
+            .map(|response| response.map(Either::Left))
+    }
+}
+
+impl<'a, E, Eng, T> Wrapper<'a, E> for NegotiatingRenderer<Eng>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: Engine<T> + 'a,
+    T: Serialize + 'a,
+{
+    type Output = (Response<Either<Eng::Body, String>>,);
+    type Endpoint = NegotiateEndpoint<E, Eng, T>;
+
+    fn wrap(self, endpoint: E) -> Self::Endpoint {
+        NegotiateEndpoint {
+            endpoint,
+            renderer: self,
+            _marker: PhantomData,
+        }
+    }
+}
+
+pub struct NegotiateEndpoint<E, Eng, T> {
+    endpoint: E,
+    renderer: NegotiatingRenderer<Eng>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<E, Eng, T> fmt::Debug for NegotiateEndpoint<E, Eng, T>
+where
+    E: fmt::Debug,
+    Eng: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NegotiateEndpoint")
+            .field("endpoint", &self.endpoint)
+            .field("renderer", &self.renderer)
+            .finish()
+    }
+}
+
+impl<'a, E, Eng, T> Endpoint<'a> for NegotiateEndpoint<E, Eng, T>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: Engine<T> + 'a,
+    T: Serialize + 'a,
+{
+    type Output = (Response<Either<Eng::Body, String>>,);
+    type Future = NegotiateFuture<'a, E, Eng>;
+
+    fn apply(&'a self, cx: &mut ApplyContext<'_>) -> ApplyResult<Self::Future> {
+        let future = self.endpoint.apply(cx)?;
+        let accept = cx
+            .input()
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|h| h.to_str().ok())
+            .map(parse_accept);
+        cx.input()
+            .response_headers()
+            .append(header::VARY, HeaderValue::from_static("accept"));
+        Ok(NegotiateFuture {
+            future,
+            accept,
+            renderer: &self.renderer,
+        })
+    }
+}
+
+pub struct NegotiateFuture<'a, E: Endpoint<'a>, Eng> {
+    future: E::Future,
+    accept: Option<Vec<MediaRange>>,
+    renderer: &'a NegotiatingRenderer<Eng>,
+}
+
+impl<'a, E, Eng> fmt::Debug for NegotiateFuture<'a, E, Eng>
+where
+    E: Endpoint<'a>,
+    E::Future: fmt::Debug,
+    Eng: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NegotiateFuture")
+            .field("future", &self.future)
+            .field("accept", &self.accept)
+            .field("renderer", &self.renderer)
+            .finish()
+    }
+}
+
+impl<'a, E, Eng, T> Future for NegotiateFuture<'a, E, Eng>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: Engine<T> + 'a,
+    T: Serialize,
+{
+    type Item = (Response<Either<Eng::Body, String>>,);
+    type Error = error::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let (value,) = try_ready!(self.future.poll());
+        self.renderer
+            .render_response(value, self.accept.as_ref().map(|a| &a[..]))
+            .map(|response| Async::Ready((response,)))
+    }
+}
+
+/// An entry of the `Accept` header.
+#[derive(Debug)]
+struct MediaRange {
+    mime: Mime,
+    /// The quality value, scaled to an integer between 0 and 1000.
+    q: u16,
+}
+
+fn parse_accept(value: &str) -> Vec<MediaRange> {
+    value
+        .split(',')
+        .filter_map(|s| s.trim().parse::<Mime>().ok())
+        .map(|mime| {
+            let q = mime
+                .get_param("q")
+                .and_then(|q| q.as_str().parse::<f32>().ok())
+                .map_or(1000, |q| (q.clamp(0.0, 1.0) * 1000.0) as u16);
+            MediaRange { mime, q }
+        })
+        .collect()
+}
+
+/// Returns the quality value of the most specific media range matching to `mime`.
+fn quality(accept: &[MediaRange], mime: &Mime) -> u16 {
+    accept
+        .iter()
+        .filter_map(|range| {
+            let specificity = match (range.mime.type_(), range.mime.subtype()) {
+                (mime::STAR, mime::STAR) => 0,
+                (ty, mime::STAR) if ty == mime.type_() => 1,
+                (ty, subty) if ty == mime.type_() && subty == mime.subtype() => 2,
+                _ => return None,
+            };
+            Some((specificity, range.q))
+        })
+        .max_by_key(|&(specificity, _)| specificity)
+        .map_or(0, |(_, q)| q)
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::engine::Engine;
+    use renderer::Renderer;
+
+    use finchers::error;
+    use finchers::prelude::*;
+    use finchers::test;
+    use http::{Request, Response};
+    use serde::Serialize;
+
+    #[derive(Debug, Serialize)]
+    struct Context {
+        name: String,
+    }
+
+    #[derive(Debug)]
+    struct DummyEngine;
+    impl Engine<Context> for DummyEngine {
+        type Body = String;
+        type Error = error::Never;
+        fn render(&self, value: Context) -> Result<Self::Body, Self::Error> {
+            Ok(format!("<p>{}</p>", value.name))
+        }
+    }
+
+    fn perform(accept: Option<&str>) -> Response<test::TestResult> {
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and(endpoint::syntax::param::<String>())
+                .and(endpoint::syntax::eos())
+                .map(|name| Context { name })
+                .wrap(Renderer::new(DummyEngine).negotiate())
+        });
+        let mut request = Request::get("/Alice");
+        if let Some(accept) = accept {
+            request.header("accept", accept);
+        }
+        runner.perform(request).unwrap()
+    }
+
+    #[test]
+    fn test_negotiate_without_accept() {
+        let response = perform(None);
+        assert_eq!(response.status().as_u16(), 200);
+        assert_matches!(
+            response.headers().get("content-type"),
+            Some(h) if h == "text/html; charset=utf-8"
+        );
+        assert_matches!(response.headers().get("vary"), Some(h) if h == "accept");
+        assert_eq!(response.body().to_utf8().unwrap(), "<p>Alice</p>");
+    }
+
+    #[test]
+    fn test_negotiate_html() {
+        let response = perform(Some("text/html,application/xhtml+xml,*/*;q=0.8"));
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.body().to_utf8().unwrap(), "<p>Alice</p>");
+    }
+
+    #[test]
+    fn test_negotiate_json() {
+        let response = perform(Some("application/json, text/*;q=0.5"));
+        assert_eq!(response.status().as_u16(), 200);
+        assert_matches!(
+            response.headers().get("content-type"),
+            Some(h) if h == "application/json"
+        );
+        assert_matches!(response.headers().get("vary"), Some(h) if h == "accept");
+        assert_eq!(response.body().to_utf8().unwrap(), r#"{"name":"Alice"}"#);
+    }
+
+    #[test]
+    fn test_negotiate_empty_accept() {
+        let response = perform(Some(""));
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.body().to_utf8().unwrap(), "<p>Alice</p>");
+    }
+
+    #[test]
+    fn test_negotiate_not_acceptable() {
+        let response = perform(Some("image/png, text/html;q=0"));
+        assert_eq!(response.status().as_u16(), 406);
+        assert_matches!(response.headers().get("vary"), Some(h) if h == "accept");
+    }
+}
@@ -0,0 +1,186 @@
+use finchers::endpoint::{ApplyContext, ApplyResult};
+use finchers::error;
+
+use http::header::{HeaderMap, HeaderValue, IntoHeaderName};
+use http::{Response, StatusCode};
+
+use renderer::{Renderer, Stage};
+
+/// A context value with the status code and the additional header fields of the response.
+///
+/// The values of this type are rendered by the renderer with the stage added by `Renderer::with_parts`.
+#[derive(Debug, Clone)]
+pub struct Rendered<T> {
+    status: StatusCode,
+    headers: HeaderMap,
+    ctx: T,
+}
+
+impl<T> Rendered<T> {
+    /// Create a new `Rendered` which will be rendered as a `200 OK` response.
+    pub fn new(ctx: T) -> Rendered<T> {
+        Rendered {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            ctx,
+        }
+    }
+
+    /// Sets the status code of the response.
+    pub fn status(mut self, status: StatusCode) -> Rendered<T> {
+        self.status = status;
+        self
+    }
+
+    /// Appends a header field to the response.
+    ///
+    /// The fields added by this method take precedence over the ones set by
+    /// the renderer, such as `Content-Type`.
+    pub fn header<K>(mut self, name: K, value: HeaderValue) -> Rendered<T>
+    where
+        K: IntoHeaderName,
+    {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Returns a reference to the inner context value.
+    pub fn context(&self) -> &T {
+        &self.ctx
+    }
+
+    /// Returns a mutable reference to the header fields added to the response.
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+}
+
+impl<Eng, S> Renderer<Eng, S> {
+    /// Adds a stage which renders the values of `Rendered<T>`.
+    pub fn with_parts(self) -> Renderer<Eng, PartsStage<S>> {
+        self.with_stage(|stage| PartsStage { stage })
+    }
+}
+
+/// A stage which applies the status code and header fields held by `Rendered<T>`.
+///
+/// The value of this type is added by `Renderer::with_parts`.
+#[derive(Debug)]
+pub struct PartsStage<S> {
+    stage: S,
+}
+
+impl<'a, Eng, S, T> Stage<'a, Eng, Rendered<T>> for PartsStage<S>
+where
+    S: Stage<'a, Eng, T>,
+{
+    type Body = S::Body;
+    type State = S::State;
+    type Prepare = S::Prepare;
+
+    fn prepare(&'a self, cx: &mut ApplyContext<'_>) -> ApplyResult<Self::Prepare> {
+        self.stage.prepare(cx)
+    }
+
+    fn render<R>(
+        &self,
+        renderer: &Renderer<Eng, R>,
+        state: Self::State,
+        rendered: Rendered<T>,
+    ) -> error::Result<Response<Self::Body>> {
+        let Rendered {
+            status,
+            headers,
+            ctx,
+        } = rendered;
+        let mut response = self.stage.render(renderer, state, ctx)?;
+        *response.status_mut() = status;
+        response.headers_mut().extend(headers);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rendered;
+    use backend::engine::Engine;
+    use renderer::Renderer;
+
+    use finchers::error;
+    use finchers::prelude::*;
+    use finchers::test;
+    use http::header::{self, HeaderValue};
+    use http::StatusCode;
+
+    #[derive(Debug)]
+    struct DummyEngine;
+    impl Engine<String> for DummyEngine {
+        type Body = String;
+        type Error = error::Never;
+        fn render(&self, value: String) -> Result<Self::Body, Self::Error> {
+            Ok(value)
+        }
+    }
+
+    #[test]
+    fn test_rendered() {
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and(endpoint::syntax::param::<String>())
+                .and(endpoint::syntax::eos())
+                .map(|name: String| {
+                    Rendered::new(format!("{} is not found", name))
+                        .status(StatusCode::NOT_FOUND)
+                        .header(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"))
+                        .header(header::SET_COOKIE, HeaderValue::from_static("a=1"))
+                        .header(header::SET_COOKIE, HeaderValue::from_static("b=2"))
+                })
+                .wrap(Renderer::new(DummyEngine).with_parts())
+        });
+
+        let response = runner.perform("/Amaterasu").unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+        assert_matches!(
+            response.headers().get("content-type"),
+            Some(h) if h == "text/html; charset=utf-8"
+        );
+        assert_matches!(
+            response.headers().get("cache-control"),
+            Some(h) if h == "no-cache"
+        );
+        assert_eq!(
+            response
+                .headers()
+                .get_all("set-cookie")
+                .iter()
+                .collect::<Vec<_>>(),
+            vec!["a=1", "b=2"]
+        );
+        assert_eq!(response.body().to_utf8().unwrap(), "Amaterasu is not found");
+    }
+
+    #[test]
+    fn test_rendered_overrides_content_type() {
+        let mut runner = test::runner({
+            endpoint::cloned("created".to_owned())
+                .map(|ctx| {
+                    Rendered::new(ctx)
+                        .status(StatusCode::CREATED)
+                        .header(header::LOCATION, HeaderValue::from_static("/items/1"))
+                        .header(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"))
+                })
+                .wrap(Renderer::new(DummyEngine).with_parts())
+        });
+
+        let response = runner.perform("/").unwrap();
+        assert_eq!(response.status().as_u16(), 201);
+        assert_matches!(
+            response.headers().get("content-type"),
+            Some(h) if h == "text/plain"
+        );
+        assert_matches!(
+            response.headers().get("location"),
+            Some(h) if h == "/items/1"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use finchers::endpoint::{ApplyContext, ApplyResult};
+use finchers::error;
+
+use http::header;
+use http::header::HeaderValue;
+use http::Response;
+
+use renderer::{Renderer, Stage};
+
+/// A trait representing a transform applied to the rendered bodies.
+///
+/// This trait is implemented by `HtmlMinifier` and the closures of the form
+/// `Fn(&HeaderValue, B) -> B`, where the first argument is the resolved content-type.
+pub trait PostProcess<B> {
+    /// Transforms the rendered body with the specified content-type.
+    fn post_process(&self, content_type: &HeaderValue, body: B) -> B;
+}
+
+impl<F, B> PostProcess<B> for F
+where
+    F: Fn(&HeaderValue, B) -> B,
+{
+    fn post_process(&self, content_type: &HeaderValue, body: B) -> B {
+        (*self)(content_type, body)
+    }
+}
+
+impl<Eng, S> Renderer<Eng, S> {
+    /// Adds a stage which transforms the rendered bodies by the specified hook.
+    pub fn post_process<P>(self, hook: P) -> Renderer<Eng, PostProcessStage<S, P>> {
+        self.with_stage(|stage| PostProcessStage { stage, hook })
+    }
+}
+
+/// A stage which applies a transform to the rendered bodies.
+///
+/// The value of this type is added by `Renderer::post_process`.
+#[derive(Debug)]
+pub struct PostProcessStage<S, P> {
+    stage: S,
+    hook: P,
+}
+
+impl<'a, Eng, S, P, T> Stage<'a, Eng, T> for PostProcessStage<S, P>
+where
+    S: Stage<'a, Eng, T>,
+    P: PostProcess<S::Body>,
+{
+    type Body = S::Body;
+    type State = S::State;
+    type Prepare = S::Prepare;
+
+    fn prepare(&'a self, cx: &mut ApplyContext<'_>) -> ApplyResult<Self::Prepare> {
+        self.stage.prepare(cx)
+    }
+
+    fn render<R>(
+        &self,
+        renderer: &Renderer<Eng, R>,
+        state: Self::State,
+        value: T,
+    ) -> error::Result<Response<Self::Body>> {
+        let response = self.stage.render(renderer, state, value)?;
+        let (parts, body) = response.into_parts();
+        let body = match parts.headers.get(header::CONTENT_TYPE) {
+            Some(content_type) => self.hook.post_process(content_type, body),
+            None => body,
+        };
+        Ok(Response::from_parts(parts, body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::engine::Engine;
+    use minify::HtmlMinifier;
+    use parts::Rendered;
+    use renderer::Renderer;
+
+    use finchers::error;
+    use finchers::prelude::*;
+    use finchers::test;
+    use http::header::HeaderValue;
+    use http::StatusCode;
+    use mime;
+
+    #[derive(Debug)]
+    struct DummyEngine;
+    impl Engine<&'static str> for DummyEngine {
+        type Body = String;
+        type Error = error::Never;
+        fn render(&self, value: &'static str) -> Result<Self::Body, Self::Error> {
+            Ok(value.to_owned())
+        }
+    }
+
+    #[test]
+    fn test_post_process_closure() {
+        let mut runner = test::runner({
+            endpoint::cloned("hello").wrap(Renderer::new(DummyEngine).post_process(
+                |content_type: &HeaderValue, body: String| {
+                    format!("{} ({})", body, content_type.to_str().unwrap())
+                },
+            ))
+        });
+        let response = runner.perform("/").unwrap();
+        assert_eq!(
+            response.body().to_utf8().unwrap(),
+            "hello (text/html; charset=utf-8)"
+        );
+    }
+
+    #[test]
+    fn test_post_process_minify() {
+        let html = "<ul>\n    <li>a</li>\n    <li>b</li>\n</ul>\n";
+
+        let mut runner = test::runner({
+            endpoint::cloned(html)
+                .wrap(Renderer::new(DummyEngine).post_process(HtmlMinifier::new()))
+        });
+        let response = runner.perform("/").unwrap();
+        assert_eq!(
+            response.body().to_utf8().unwrap(),
+            "<ul> <li>a</li> <li>b</li> </ul>"
+        );
+
+        // the minifier is skipped unless the content-type is text/html.
+        let mut runner = test::runner({
+            endpoint::cloned(html).wrap(
+                Renderer::new(DummyEngine)
+                    .content_type(&mime::TEXT_PLAIN_UTF_8)
+                    .post_process(HtmlMinifier::new()),
+            )
+        });
+        let response = runner.perform("/").unwrap();
+        assert_eq!(response.body().to_utf8().unwrap(), html);
+    }
+
+    #[test]
+    fn test_post_process_with_parts() {
+        let mut runner = test::runner({
+            endpoint::cloned("<p>\n    not found\n</p>\n")
+                .map(|html| Rendered::new(html).status(StatusCode::NOT_FOUND))
+                .wrap(
+                    Renderer::new(DummyEngine)
+                        .post_process(HtmlMinifier::new())
+                        .with_parts(),
+                )
+        });
+        let response = runner.perform("/").unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+        assert_eq!(response.body().to_utf8().unwrap(), "<p> not found </p>");
+    }
+}
@@ -5,13 +5,16 @@ use finchers::error;
 use std::fmt;
 use std::marker::PhantomData;
 
+use failure;
+use futures::future::{self, FutureResult, Join};
 use futures::{Async, Future, Poll};
 use http::header;
 use http::header::HeaderValue;
 use http::Response;
 use mime::Mime;
 
-use backend::engine::Engine;
+use backend::boxed::{BoxBody, BoxEngine};
+use backend::engine::{Engine, Validate};
 
 lazy_static! {
     static ref DEFAULT_CONTENT_TYPE: HeaderValue =
@@ -20,10 +23,16 @@ lazy_static! {
 
 /// A struct which renders a context value to an HTTP response
 /// using the specified template engine.
+///
+/// The response can be further processed by the stages added by the methods
+/// such as `with_parts` and `etag`. The stages are applied in the order they
+/// are added, so the first one receives the response rendered by the engine
+/// and the last one receives the output of the endpoint.
 #[derive(Debug)]
-pub struct Renderer<Eng> {
+pub struct Renderer<Eng, S = ()> {
     engine: Eng,
     content_type: Option<HeaderValue>,
+    stage: S,
 }
 
 impl<Eng> Renderer<Eng> {
@@ -32,9 +41,12 @@ impl<Eng> Renderer<Eng> {
         Renderer {
             engine,
             content_type: None,
+            stage: (),
         }
     }
+}
 
+impl<Eng, S> Renderer<Eng, S> {
     /// Returns a reference to the inner template engine.
     pub fn engine(&self) -> &Eng {
         &self.engine
@@ -46,7 +58,7 @@ impl<Eng> Renderer<Eng> {
     }
 
     /// Sets the value of content-type used in the rendered HTTP responses.
-    pub fn content_type(mut self, value: &Mime) -> Renderer<Eng> {
+    pub fn content_type(mut self, value: &Mime) -> Renderer<Eng, S> {
         self.content_type = Some(
             value
                 .as_ref()
@@ -56,25 +68,87 @@ impl<Eng> Renderer<Eng> {
         self
     }
 
-    fn get_content_type<T>(&self, value: &T) -> HeaderValue
+    /// Checks the configuration of the inner engine, such as the existence of the template.
+    pub fn validate(&self) -> Result<(), failure::Error>
+    where
+        Eng: Validate,
+    {
+        self.engine.validate()
+    }
+
+    /// Validates the inner engine and returns itself if it succeeds.
+    ///
+    /// This method is useful to check all renderers while building the endpoint,
+    /// before the server is started:
+    ///
+    /// ```ignore
+    /// let endpoint = path!(@get /)
+    ///     .map(|| Context { ... })
+    ///     .wrap(finchers_template::tera(tera, "index.html").validated()?);
+    /// ```
+    pub fn validated(self) -> Result<Renderer<Eng, S>, failure::Error>
+    where
+        Eng: Validate,
+    {
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Converts the inner engine into a type-erased `BoxEngine`.
+    ///
+    /// The content-type set by `content_type` is kept, while `Validate` is not
+    /// forwarded to the inner engine and the boxed engine always fails to
+    /// validate. Call `validated` before boxing, or create the engine by
+    /// `BoxEngine::with_validation` to keep it.
+    pub fn boxed<T>(self) -> Renderer<BoxEngine<T>, S>
+    where
+        Eng: Engine<T> + Send + Sync + 'static,
+        Eng::Body: BoxBody,
+    {
+        Renderer {
+            engine: BoxEngine::new(self.engine),
+            content_type: self.content_type,
+            stage: self.stage,
+        }
+    }
+
+    /// Adds a stage which wraps the current ones.
+    pub(crate) fn with_stage<S2>(self, f: impl FnOnce(S) -> S2) -> Renderer<Eng, S2> {
+        Renderer {
+            engine: self.engine,
+            content_type: self.content_type,
+            stage: f(self.stage),
+        }
+    }
+
+    pub(crate) fn get_content_type<T>(&self, value: &T) -> HeaderValue
     where
         Eng: Engine<T>,
     {
         self.content_type
             .clone()
-            .or_else(|| self.engine.content_type_hint(&value))
+            .or_else(|| self.engine.content_type_hint(value))
             .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.clone())
     }
 
-    fn render_response<T>(&self, value: T) -> error::Result<Response<Eng::Body>>
+    pub(crate) fn render_response<T>(&self, value: T) -> error::Result<Response<Eng::Body>>
     where
         Eng: Engine<T>,
     {
-        let content_type = self.get_content_type(&value);
-        let body = self
-            .engine
-            .render(value)
-            .map_err(|err| error::Error::from(err.into()))?;
+        let result = match self.content_type {
+            Some(ref content_type) => self
+                .engine
+                .render(value)
+                .map(|body| (content_type.clone(), body)),
+            None => self
+                .engine
+                .render_with_hint(value)
+                .map(|(content_type, body)| {
+                    let content_type = content_type.unwrap_or_else(|| DEFAULT_CONTENT_TYPE.clone());
+                    (content_type, body)
+                }),
+        };
+        let (content_type, body) = result.map_err(|err| error::Error::from(err.into()))?;
         let mut response = Response::new(body);
         response
             .headers_mut()
@@ -83,14 +157,64 @@ impl<Eng> Renderer<Eng> {
     }
 }
 
-impl<'a, E, Eng, T> Wrapper<'a, E> for Renderer<Eng>
+/// A trait representing a stage of `Renderer` which processes the rendered responses.
+///
+/// A stage wraps the stages added before it, and receives the context value
+/// of type `T` from the endpoint. The base stage `()` renders the value by
+/// the engine.
+pub trait Stage<'a, Eng, T> {
+    /// The type of body in the processed responses.
+    type Body;
+
+    /// The per-request state extracted when the endpoint is applied.
+    type State;
+
+    /// The future which returns the per-request state.
+    type Prepare: Future<Item = Self::State, Error = error::Error>;
+
+    #[doc(hidden)]
+    fn prepare(&'a self, cx: &mut ApplyContext<'_>) -> ApplyResult<Self::Prepare>;
+
+    #[doc(hidden)]
+    fn render<R>(
+        &self,
+        renderer: &Renderer<Eng, R>,
+        state: Self::State,
+        value: T,
+    ) -> error::Result<Response<Self::Body>>;
+}
+
+impl<'a, Eng, T> Stage<'a, Eng, T> for ()
+where
+    Eng: Engine<T>,
+{
+    type Body = Eng::Body;
+    type State = ();
+    type Prepare = FutureResult<(), error::Error>;
+
+    fn prepare(&'a self, _: &mut ApplyContext<'_>) -> ApplyResult<Self::Prepare> {
+        Ok(future::ok(()))
+    }
+
+    fn render<R>(
+        &self,
+        renderer: &Renderer<Eng, R>,
+        _: (),
+        value: T,
+    ) -> error::Result<Response<Self::Body>> {
+        renderer.render_response(value)
+    }
+}
+
+impl<'a, E, Eng, S, T> Wrapper<'a, E> for Renderer<Eng, S>
 where
     E: Endpoint<'a, Output = (T,)>,
-    Eng: Engine<T> + 'a,
+    Eng: 'a,
+    S: Stage<'a, Eng, T> + 'a,
     T: 'a,
 {
-    type Output = (Response<Eng::Body>,);
-    type Endpoint = RenderEndpoint<E, Eng, T>;
+    type Output = (Response<S::Body>,);
+    type Endpoint = RenderEndpoint<E, Eng, S, T>;
 
     fn wrap(self, endpoint: E) -> Self::Endpoint {
         RenderEndpoint {
@@ -101,19 +225,17 @@ where
     }
 }
 
-pub struct RenderEndpoint<E, Eng, T>
-where
-    Eng: Engine<T>,
-{
+pub struct RenderEndpoint<E, Eng, S, T> {
     endpoint: E,
-    renderer: Renderer<Eng>,
+    renderer: Renderer<Eng, S>,
     _marker: PhantomData<fn() -> T>,
 }
 
-impl<E, Eng, T> fmt::Debug for RenderEndpoint<E, Eng, T>
+impl<E, Eng, S, T> fmt::Debug for RenderEndpoint<E, Eng, S, T>
 where
     E: fmt::Debug,
-    Eng: Engine<T> + fmt::Debug,
+    Eng: fmt::Debug,
+    S: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("RenderEndpoint")
@@ -123,38 +245,38 @@ where
     }
 }
 
-impl<'a, E, Eng, T> Endpoint<'a> for RenderEndpoint<E, Eng, T>
+impl<'a, E, Eng, S, T> Endpoint<'a> for RenderEndpoint<E, Eng, S, T>
 where
     E: Endpoint<'a, Output = (T,)>,
-    Eng: Engine<T> + 'a,
+    Eng: 'a,
+    S: Stage<'a, Eng, T> + 'a,
     T: 'a,
 {
-    type Output = (Response<Eng::Body>,);
-    type Future = RenderFuture<'a, E, Eng, T>;
+    type Output = (Response<S::Body>,);
+    type Future = RenderFuture<'a, E, Eng, S, T>;
 
     fn apply(&'a self, cx: &mut ApplyContext<'_>) -> ApplyResult<Self::Future> {
+        // The stages are prepared before the endpoint consumes the path segments.
+        let state = self.renderer.stage.prepare(cx)?;
+        let future = self.endpoint.apply(cx)?;
         Ok(RenderFuture {
-            future: self.endpoint.apply(cx)?,
+            future: future.join(state),
             renderer: &self.renderer,
-            _marker: PhantomData,
         })
     }
 }
 
-pub struct RenderFuture<'a, E: Endpoint<'a>, Eng: 'a, T: 'a>
-where
-    Eng: Engine<T>,
-{
-    future: E::Future,
-    renderer: &'a Renderer<Eng>,
-    _marker: PhantomData<fn() -> T>,
+pub struct RenderFuture<'a, E: Endpoint<'a>, Eng, S: Stage<'a, Eng, T>, T> {
+    future: Join<E::Future, S::Prepare>,
+    renderer: &'a Renderer<Eng, S>,
 }
 
-impl<'a, E, Eng, T> fmt::Debug for RenderFuture<'a, E, Eng, T>
+impl<'a, E, Eng, S, T> fmt::Debug for RenderFuture<'a, E, Eng, S, T>
 where
-    E: Endpoint<'a> + fmt::Debug,
-    E::Future: fmt::Debug,
-    Eng: Engine<T> + fmt::Debug,
+    E: Endpoint<'a>,
+    Eng: fmt::Debug,
+    S: Stage<'a, Eng, T> + fmt::Debug,
+    Join<E::Future, S::Prepare>: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("RenderFuture")
@@ -164,18 +286,19 @@ where
     }
 }
 
-impl<'a, E, Eng, T> Future for RenderFuture<'a, E, Eng, T>
+impl<'a, E, Eng, S, T> Future for RenderFuture<'a, E, Eng, S, T>
 where
     E: Endpoint<'a, Output = (T,)>,
-    Eng: Engine<T> + 'a,
+    S: Stage<'a, Eng, T>,
 {
-    type Item = (Response<Eng::Body>,);
+    type Item = (Response<S::Body>,);
     type Error = error::Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let (value,) = try_ready!(self.future.poll());
+        let ((value,), state) = try_ready!(self.future.poll());
         self.renderer
-            .render_response(value)
+            .stage
+            .render(self.renderer, state, value)
             .map(|response| Async::Ready((response,)))
     }
 }
@@ -183,7 +306,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::Renderer;
-    use backend::engine::{Engine, EngineImpl};
+    use backend::engine::Engine;
 
     use finchers::error;
     use finchers::prelude::*;
@@ -193,8 +316,7 @@ mod tests {
     #[test]
     fn test_renderer() {
         struct DummyEngine;
-        impl<T: ToString> Engine<T> for DummyEngine {}
-        impl<T: ToString> EngineImpl<T> for DummyEngine {
+        impl<T: ToString> Engine<T> for DummyEngine {
             type Body = String;
             type Error = error::Never;
             fn render(&self, value: T) -> Result<Self::Body, Self::Error> {
@@ -217,5 +339,4 @@ mod tests {
         );
         assert_eq!(response.body().to_utf8().unwrap(), "Amaterasu");
     }
-
 }
@@ -0,0 +1,448 @@
+use finchers::endpoint::wrapper::Wrapper;
+use finchers::endpoint::{ApplyContext, ApplyResult, Endpoint};
+use finchers::error;
+
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use failure;
+use futures::executor::{self, Notify};
+use futures::sync::mpsc;
+use futures::{stream, Async, Future, Poll, Sink, Stream};
+use http::header;
+use http::header::HeaderValue;
+use http::Response;
+use hyper::Body;
+
+use backend::engine::StreamingEngine;
+use blocking::{Job, RenderPool};
+use renderer::Renderer;
+
+const DEFAULT_CHUNK_SIZE: usize = 8 * 1024;
+const DEFAULT_SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+type Chunk = Result<Bytes, failure::Error>;
+
+impl<Eng> Renderer<Eng> {
+    /// Converts this renderer into one which sends the rendered output as a chunked response body.
+    ///
+    /// The templates are rendered on the specified worker pool, and the output
+    /// is flushed to the client each time `chunk_size` bytes are written.
+    /// The response is returned as soon as the first chunk is available, so an
+    /// error occurred after that point aborts the response body.
+    pub fn streaming(self, pool: &RenderPool) -> StreamingRenderer<Eng> {
+        StreamingRenderer {
+            renderer: Arc::new(self),
+            pool: pool.clone(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            send_timeout: DEFAULT_SEND_TIMEOUT,
+        }
+    }
+}
+
+/// A renderer which streams the rendered output to the client.
+///
+/// The value of this type is created by `Renderer::streaming`.
+#[derive(Debug)]
+pub struct StreamingRenderer<Eng> {
+    renderer: Arc<Renderer<Eng>>,
+    pool: RenderPool,
+    chunk_size: usize,
+    send_timeout: Duration,
+}
+
+impl<Eng> StreamingRenderer<Eng> {
+    /// Sets the number of bytes buffered before a chunk is sent to the client.
+    ///
+    /// The default value is 8 KiB.
+    pub fn chunk_size(mut self, chunk_size: usize) -> StreamingRenderer<Eng> {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets the maximum duration for which a worker thread waits for the client to receive a chunk.
+    ///
+    /// The worker thread is occupied while the client is not reading the body,
+    /// so the rendering is aborted once it waits longer than this duration.
+    /// The default value is 30 seconds.
+    pub fn send_timeout(mut self, timeout: Duration) -> StreamingRenderer<Eng> {
+        self.send_timeout = timeout;
+        self
+    }
+}
+
+impl<'a, E, Eng, T> Wrapper<'a, E> for StreamingRenderer<Eng>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: StreamingEngine<T> + Send + Sync + 'static,
+    T: Send + 'static,
+{
+    type Output = (Response<Body>,);
+    type Endpoint = StreamingRenderEndpoint<E, Eng, T>;
+
+    fn wrap(self, endpoint: E) -> Self::Endpoint {
+        StreamingRenderEndpoint {
+            endpoint,
+            renderer: self,
+            _marker: PhantomData,
+        }
+    }
+}
+
+pub struct StreamingRenderEndpoint<E, Eng, T> {
+    endpoint: E,
+    renderer: StreamingRenderer<Eng>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<E, Eng, T> fmt::Debug for StreamingRenderEndpoint<E, Eng, T>
+where
+    E: fmt::Debug,
+    Eng: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamingRenderEndpoint")
+            .field("endpoint", &self.endpoint)
+            .field("renderer", &self.renderer)
+            .finish()
+    }
+}
+
+impl<'a, E, Eng, T> Endpoint<'a> for StreamingRenderEndpoint<E, Eng, T>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: StreamingEngine<T> + Send + Sync + 'static,
+    T: Send + 'static,
+{
+    type Output = (Response<Body>,);
+    type Future = StreamingRenderFuture<'a, E, Eng>;
+
+    fn apply(&'a self, cx: &mut ApplyContext<'_>) -> ApplyResult<Self::Future> {
+        Ok(StreamingRenderFuture {
+            state: RenderState::Endpoint(self.endpoint.apply(cx)?),
+            renderer: &self.renderer,
+        })
+    }
+}
+
+pub struct StreamingRenderFuture<'a, E: Endpoint<'a>, Eng> {
+    state: RenderState<'a, E>,
+    renderer: &'a StreamingRenderer<Eng>,
+}
+
+enum RenderState<'a, E: Endpoint<'a>> {
+    Endpoint(E::Future),
+    Submitting(Job, HeaderValue, Receiver),
+    Rendering(HeaderValue, Receiver),
+    Done,
+}
+
+impl<'a, E, Eng> fmt::Debug for StreamingRenderFuture<'a, E, Eng>
+where
+    E: Endpoint<'a>,
+    Eng: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            RenderState::Endpoint(..) => "Endpoint",
+            RenderState::Submitting(..) => "Submitting",
+            RenderState::Rendering(..) => "Rendering",
+            RenderState::Done => "Done",
+        };
+        f.debug_struct("StreamingRenderFuture")
+            .field("state", &state)
+            .field("renderer", &self.renderer)
+            .finish()
+    }
+}
+
+impl<'a, E, Eng, T> Future for StreamingRenderFuture<'a, E, Eng>
+where
+    E: Endpoint<'a, Output = (T,)>,
+    Eng: StreamingEngine<T> + Send + Sync + 'static,
+    T: Send + 'static,
+{
+    type Item = (Response<Body>,);
+    type Error = error::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            self.state = match mem::replace(&mut self.state, RenderState::Done) {
+                RenderState::Endpoint(mut future) => match future.poll()? {
+                    Async::Ready((value,)) => {
+                        let content_type = self.renderer.renderer.get_content_type(&value);
+                        let (tx, rx) = mpsc::channel(1);
+                        let completed = Arc::new(AtomicBool::new(false));
+                        let rx = Receiver {
+                            rx,
+                            completed: completed.clone(),
+                        };
+                        let renderer = self.renderer.renderer.clone();
+                        let mut writer = ChunkWriter::new(
+                            tx,
+                            self.renderer.chunk_size,
+                            self.renderer.send_timeout,
+                        );
+                        let job: Job = Box::new(move || {
+                            let result = renderer
+                                .engine()
+                                .render_to_write(value, &mut writer)
+                                .map_err(Into::into)
+                                .and_then(|()| io::Write::flush(&mut writer).map_err(Into::into));
+                            match result {
+                                Ok(()) => completed.store(true, Ordering::SeqCst),
+                                Err(err) => writer.send_error(err),
+                            }
+                        });
+                        RenderState::Submitting(job, content_type, rx)
+                    }
+                    Async::NotReady => {
+                        self.state = RenderState::Endpoint(future);
+                        return Ok(Async::NotReady);
+                    }
+                },
+                RenderState::Submitting(job, content_type, rx) => {
+                    match self.renderer.pool.poll_submit(job) {
+                        Ok(()) => RenderState::Rendering(content_type, rx),
+                        Err(job) => {
+                            self.state = RenderState::Submitting(job, content_type, rx);
+                            return Ok(Async::NotReady);
+                        }
+                    }
+                }
+                RenderState::Rendering(content_type, mut rx) => {
+                    // Wait for the first chunk so that the errors before it
+                    // are reported as an error response.
+                    let first = match rx.poll() {
+                        Ok(Async::Ready(first)) => first,
+                        Ok(Async::NotReady) => {
+                            self.state = RenderState::Rendering(content_type, rx);
+                            return Ok(Async::NotReady);
+                        }
+                        Err(err) => return Err(err.into()),
+                    };
+                    let rest = rx.map_err(failure::Error::compat);
+                    let body = Body::wrap_stream(stream::iter_ok(first).chain(rest));
+                    let mut response = Response::new(body);
+                    response
+                        .headers_mut()
+                        .insert(header::CONTENT_TYPE, content_type);
+                    return Ok(Async::Ready((response,)));
+                }
+                RenderState::Done => panic!("cannot poll the future twice"),
+            };
+        }
+    }
+}
+
+/// The receiving half of the chunks, which fails if the rendering is aborted.
+struct Receiver {
+    rx: mpsc::Receiver<Chunk>,
+    completed: Arc<AtomicBool>,
+}
+
+impl Stream for Receiver {
+    type Item = Bytes;
+    type Error = failure::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self.rx.poll() {
+            Ok(Async::Ready(Some(chunk))) => chunk.map(|chunk| Async::Ready(Some(chunk))),
+            Ok(Async::Ready(None)) if self.completed.load(Ordering::SeqCst) => {
+                Ok(Async::Ready(None))
+            }
+            // The sender is dropped without completing the output, e.g. by a timeout or a panic.
+            Ok(Async::Ready(None)) => Err(failure::err_msg("the rendering was aborted")),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(()) => unreachable!("mpsc::Receiver never fails"),
+        }
+    }
+}
+
+/// A writer which sends the written bytes to the response body in chunks.
+struct ChunkWriter {
+    buf: Vec<u8>,
+    chunk_size: usize,
+    send_timeout: Duration,
+    tx: Option<mpsc::Sender<Chunk>>,
+}
+
+impl ChunkWriter {
+    fn new(tx: mpsc::Sender<Chunk>, chunk_size: usize, send_timeout: Duration) -> ChunkWriter {
+        ChunkWriter {
+            buf: Vec::with_capacity(chunk_size),
+            chunk_size,
+            send_timeout,
+            tx: Some(tx),
+        }
+    }
+
+    /// Sends a chunk, blocking the current thread until the client receives
+    /// the previous one or `send_timeout` elapses.
+    fn send(&mut self, chunk: Chunk) -> io::Result<()> {
+        let tx = self
+            .tx
+            .take()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        let deadline = Instant::now() + self.send_timeout;
+        let notify = Arc::new(ThreadNotify(thread::current()));
+        let mut send = executor::spawn(tx.send(chunk));
+        loop {
+            match send.poll_future_notify(&notify, 0) {
+                Ok(Async::Ready(tx)) => {
+                    self.tx = Some(tx);
+                    return Ok(());
+                }
+                Ok(Async::NotReady) => {}
+                Err(..) => return Err(io::Error::from(io::ErrorKind::BrokenPipe)),
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the client did not receive the response body in time",
+                ));
+            }
+            thread::park_timeout(deadline - now);
+        }
+    }
+
+    /// Reports the error to the client, if the sender is still alive.
+    fn send_error(&mut self, err: failure::Error) {
+        if self.tx.is_some() {
+            let _ = self.send(Err(err));
+        }
+    }
+}
+
+impl io::Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        if self.buf.len() >= self.chunk_size {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(mem::replace(
+            &mut self.buf,
+            Vec::with_capacity(self.chunk_size),
+        ));
+        self.send(Ok(chunk))
+    }
+}
+
+struct ThreadNotify(thread::Thread);
+
+impl Notify for ThreadNotify {
+    fn notify(&self, _: usize) {
+        self.0.unpark();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::engine::{Engine, StreamingEngine};
+    use blocking::RenderPool;
+    use renderer::Renderer;
+
+    use super::ChunkWriter;
+
+    use failure;
+    use finchers::prelude::*;
+    use finchers::test;
+    use futures::sync::mpsc;
+    use futures::Stream;
+    use std::io::{self, Write};
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    struct LinesEngine;
+    impl Engine<u32> for LinesEngine {
+        type Body = String;
+        type Error = failure::Error;
+        fn render(&self, _: u32) -> Result<Self::Body, Self::Error> {
+            unreachable!()
+        }
+    }
+    impl StreamingEngine<u32> for LinesEngine {
+        fn render_to_write(&self, n: u32, writer: &mut dyn io::Write) -> Result<(), Self::Error> {
+            if n == 0 {
+                return Err(failure::err_msg("no lines"));
+            }
+            for i in 0..n {
+                writeln!(writer, "line {}", i)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_streaming() {
+        let pool = RenderPool::new(1, 0);
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and(endpoint::syntax::param::<u32>())
+                .and(endpoint::syntax::eos())
+                .wrap(Renderer::new(LinesEngine).streaming(&pool).chunk_size(8))
+        });
+
+        let response = runner.perform("/3").unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_matches!(
+            response.headers().get("content-type"),
+            Some(h) if h == "text/html; charset=utf-8"
+        );
+        assert_eq!(
+            response.body().to_utf8().unwrap(),
+            "line 0\nline 1\nline 2\n"
+        );
+        // the output is sent in multiple chunks, rather than buffered as a whole.
+        assert!(response.body().is_chunked());
+        assert!(response.body().chunks().unwrap().len() > 1);
+    }
+
+    #[test]
+    fn test_streaming_error_before_first_chunk() {
+        let pool = RenderPool::new(1, 0);
+        let mut runner = test::runner({
+            endpoint::syntax::verb::get()
+                .and(endpoint::syntax::param::<u32>())
+                .and(endpoint::syntax::eos())
+                .wrap(Renderer::new(LinesEngine).streaming(&pool))
+        });
+
+        let response = runner.perform("/0").unwrap();
+        assert_eq!(response.status().as_u16(), 500);
+    }
+
+    #[test]
+    fn test_send_timeout() {
+        let (tx, rx) = mpsc::channel(1);
+        let mut writer = ChunkWriter::new(tx, 1, Duration::from_millis(10));
+
+        // the client does not receive any chunk, so the writer gives up
+        // once the channel is full.
+        let err = (0..10)
+            .map(|_| writer.write_all(b"x"))
+            .find(Result::is_err)
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        drop(writer);
+
+        let chunks: Vec<_> = rx.wait().map(|chunk| chunk.unwrap().unwrap()).collect();
+        assert!(!chunks.is_empty() && chunks.len() < 10);
+    }
+}
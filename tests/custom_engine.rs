@@ -0,0 +1,75 @@
+extern crate failure;
+extern crate finchers;
+extern crate finchers_template;
+extern crate http;
+#[macro_use]
+extern crate matches;
+
+use finchers::prelude::*;
+use finchers::test;
+use finchers_template::backend::Engine;
+use finchers_template::Renderer;
+use http::header::HeaderValue;
+
+/// An engine defined outside of `finchers_template`.
+#[derive(Debug)]
+struct ReverseEngine;
+
+impl Engine<String> for ReverseEngine {
+    type Body = String;
+    type Error = failure::Error;
+
+    fn content_type_hint(&self, _: &String) -> Option<HeaderValue> {
+        Some(HeaderValue::from_static("text/plain; charset=utf-8"))
+    }
+
+    fn render(&self, value: String) -> Result<Self::Body, Self::Error> {
+        if value.is_empty() {
+            return Err(failure::err_msg("empty context"));
+        }
+        Ok(value.chars().rev().collect())
+    }
+}
+
+#[test]
+fn test_custom_engine() {
+    let mut runner = test::runner({
+        endpoint::syntax::verb::get()
+            .and(endpoint::syntax::param::<String>())
+            .and(endpoint::syntax::eos())
+            .wrap(Renderer::new(ReverseEngine))
+    });
+
+    let response = runner.perform("/Amaterasu").unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_matches!(
+        response.headers().get("content-type"),
+        Some(h) if h == "text/plain; charset=utf-8"
+    );
+    assert_eq!(response.body().to_utf8().unwrap(), "usaretamA");
+}
+
+#[test]
+fn test_custom_engine_overridden_content_type() {
+    let mut runner = test::runner({
+        endpoint::syntax::verb::get()
+            .and(endpoint::syntax::param::<String>())
+            .and(endpoint::syntax::eos())
+            .wrap(Renderer::new(ReverseEngine).content_type(&"text/html".parse().unwrap()))
+    });
+
+    let response = runner.perform("/Amaterasu").unwrap();
+    assert_matches!(
+        response.headers().get("content-type"),
+        Some(h) if h == "text/html"
+    );
+}
+
+#[test]
+fn test_custom_engine_error() {
+    let mut runner =
+        test::runner(endpoint::cloned(String::new()).wrap(Renderer::new(ReverseEngine)));
+
+    let response = runner.perform("/").unwrap();
+    assert_eq!(response.status().as_u16(), 500);
+}